@@ -0,0 +1,484 @@
+use std::cmp::Ordering;
+
+use slotmap::{new_key_type, Key, SlotMap};
+
+use crate::avl::{Avl, NodeDirection};
+
+new_key_type! { pub struct NodeKey; }
+type Nodes<K> = SlotMap<NodeKey, Node<K>>;
+
+struct Node<K> {
+    key: K,
+    longer_side: NodeDirection,
+    children: [NodeKey; 2],
+}
+
+impl<K> Node<K> {
+    fn new(key: K) -> Self {
+        Node {
+            key,
+            longer_side: NodeDirection::None,
+            children: [NodeKey::null(); 2],
+        }
+    }
+}
+
+impl<K: Ord> Node<K> {
+    fn dir(&self, key: &K) -> NodeDirection {
+        match key.cmp(&self.key) {
+            Ordering::Less => NodeDirection::Left,
+            Ordering::Greater => NodeDirection::Right,
+            Ordering::Equal => NodeDirection::None,
+        }
+    }
+}
+
+// same as Avl, but the nodes are allocated from an arena instead of being boxed individually
+pub struct ArenaAvl<K> {
+    nodes: Nodes<K>,
+    root: NodeKey,
+}
+
+impl<K> ArenaAvl<K> {
+    pub fn new() -> Self {
+        ArenaAvl::with_capacity(0)
+    }
+
+    // room for capacity nodes is allocated upfront
+    pub fn with_capacity(capacity: usize) -> Self {
+        ArenaAvl {
+            nodes: Nodes::with_capacity_and_key(capacity),
+            root: NodeKey::null(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+}
+
+impl<K> Default for ArenaAvl<K> {
+    fn default() -> Self {
+        ArenaAvl::new()
+    }
+}
+
+impl<K> Avl<K> {
+    pub fn with_arena() -> ArenaAvl<K> {
+        ArenaAvl::new()
+    }
+}
+
+// move the !dir-child of node_key to the top, while keeping the order
+// returns the new top
+fn rotate<K>(nodes: &mut Nodes<K>, node_key: NodeKey, dir: NodeDirection) -> NodeKey {
+    let new_root_key = nodes[node_key].children[!dir as usize];
+    nodes[node_key].children[!dir as usize] = nodes[new_root_key].children[dir as usize];
+    nodes[new_root_key].children[dir as usize] = node_key;
+    new_root_key
+}
+
+// see Avl::rebalance; also returns the new top of the subtree
+fn rebalance<K>(nodes: &mut Nodes<K>, node_key: NodeKey, dir: NodeDirection) -> (NodeKey, bool) {
+    let node = &mut nodes[node_key];
+    match node.longer_side {
+        // the node was balanced
+        NodeDirection::None => {
+            // it becomes unbalanced, and the height is still increased
+            node.longer_side = dir;
+            (node_key, true)
+        }
+        // the node was already unbalanced, in the opposite direction
+        longer if longer != dir => {
+            // the node has been rebalanced, the height has not changed
+            node.longer_side = NodeDirection::None;
+            (node_key, false)
+        }
+        // the node was already unbalanced, in the same direction
+        _ => {
+            let child_key = node.children[dir as usize];
+            match nodes[child_key].longer_side {
+                // the child is balanced
+                NodeDirection::None => {
+                    nodes[node_key].longer_side = dir;
+                    nodes[child_key].longer_side = !dir;
+                    // this cannot happen during insertion
+                    (rotate(nodes, node_key, !dir), true)
+                }
+                // the child is unbalanced in the same direction
+                cdir if cdir == dir => {
+                    nodes[node_key].longer_side = NodeDirection::None;
+                    nodes[child_key].longer_side = NodeDirection::None;
+                    (rotate(nodes, node_key, !dir), false)
+                }
+                // the child is unbalanced in the opposite direction
+                _ => {
+                    let grandchild_key = nodes[child_key].children[!dir as usize];
+                    match nodes[grandchild_key].longer_side {
+                        NodeDirection::None => {
+                            nodes[node_key].longer_side = NodeDirection::None;
+                            nodes[child_key].longer_side = NodeDirection::None;
+                        }
+                        gdir if gdir == dir => {
+                            nodes[node_key].longer_side = !dir;
+                            nodes[child_key].longer_side = NodeDirection::None;
+                        }
+                        _ => {
+                            nodes[node_key].longer_side = NodeDirection::None;
+                            nodes[child_key].longer_side = dir;
+                        }
+                    }
+                    nodes[grandchild_key].longer_side = NodeDirection::None;
+                    // need two rotations
+                    nodes[node_key].children[dir as usize] = rotate(nodes, child_key, dir);
+                    (rotate(nodes, node_key, !dir), false)
+                }
+            }
+        }
+    }
+}
+
+impl<K: std::fmt::Display> ArenaAvl<K> {
+    pub fn print(&self) {
+        fn aux<K: std::fmt::Display>(nodes: &Nodes<K>, node_key: NodeKey, indent: usize) {
+            let prefix = "    ".repeat(indent);
+            if let Some(node) = nodes.get(node_key) {
+                println!("{}- {} ({:?})", prefix, node.key, node.longer_side);
+                aux(nodes, node.children[0], indent + 1);
+                aux(nodes, node.children[1], indent + 1);
+            } else {
+                println!("{}-", prefix);
+            }
+        }
+        aux(&self.nodes, self.root, 0);
+    }
+}
+
+impl<K: Ord> ArenaAvl<K> {
+    fn check(&self) {
+        // returns the height
+        fn aux<K: Ord>(
+            nodes: &Nodes<K>,
+            node_key: NodeKey,
+            min: Option<&K>,
+            max: Option<&K>,
+        ) -> i32 {
+            let Some(node) = nodes.get(node_key) else {
+                return 1;
+            };
+            if let Some(min) = min {
+                assert!(node.key > *min);
+            }
+            if let Some(max) = max {
+                assert!(node.key < *max);
+            }
+            let lh = aux(nodes, node.children[0], min, Some(&node.key));
+            let lr = aux(nodes, node.children[1], Some(&node.key), max);
+            match lr - lh {
+                1 => assert_eq!(node.longer_side, NodeDirection::Right),
+                -1 => assert_eq!(node.longer_side, NodeDirection::Left),
+                0 => assert_eq!(node.longer_side, NodeDirection::None),
+                _ => unreachable!(),
+            }
+            lh.max(lr) + 1
+        }
+        aux(&self.nodes, self.root, None, None);
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        let mut node_key = self.root;
+        while let Some(node) = self.nodes.get(node_key) {
+            match node.dir(&key) {
+                NodeDirection::None => return true,
+                dir => node_key = node.children[dir as usize],
+            }
+        }
+        false
+    }
+
+    pub fn insert(&mut self, key: K) {
+        self.insert_no_check(key);
+        self.check();
+    }
+
+    // insert without validating the whole tree, for benchmarks
+    fn insert_no_check(&mut self, key: K) {
+        // returns the new top of the subtree and whether its height has increased
+        fn aux<K: Ord>(nodes: &mut Nodes<K>, node_key: NodeKey, key: K) -> (NodeKey, bool) {
+            let Some(node) = nodes.get(node_key) else {
+                return (nodes.insert(Node::new(key)), true);
+            };
+            match node.dir(&key) {
+                NodeDirection::None => (node_key, false),
+                dir => {
+                    let child_key = node.children[dir as usize];
+                    let (child_key, increased) = aux(nodes, child_key, key);
+                    nodes[node_key].children[dir as usize] = child_key;
+                    if !increased {
+                        return (node_key, false);
+                    }
+                    // the height has increased, we need to rebalance
+                    rebalance(nodes, node_key, dir)
+                }
+            }
+        }
+        (self.root, _) = aux(&mut self.nodes, self.root, key);
+    }
+
+    pub fn remove(&mut self, key: K) {
+        // detach the leftmost node of the subtree
+        // returns the new top of the subtree, the detached node, and whether the height has decreased
+        fn pop_leftmost<K>(nodes: &mut Nodes<K>, node_key: NodeKey) -> (NodeKey, NodeKey, bool) {
+            let left_key = nodes[node_key].children[0];
+            if nodes.get(left_key).is_none() {
+                let right_key = nodes[node_key].children[1];
+                nodes[node_key].children[1] = NodeKey::null();
+                return (right_key, node_key, true);
+            }
+            let (left_key, leftmost_key, decreased) = pop_leftmost(nodes, left_key);
+            nodes[node_key].children[0] = left_key;
+            if !decreased {
+                return (node_key, leftmost_key, false);
+            }
+            // we have reduced the height by one on the left, we need to rebalance
+            let (node_key, increased) = rebalance(nodes, node_key, NodeDirection::Right);
+            (node_key, leftmost_key, !increased)
+        }
+        // returns the new top of the subtree and whether its height has decreased
+        fn aux<K: Ord>(nodes: &mut Nodes<K>, node_key: NodeKey, key: K) -> (NodeKey, bool) {
+            let Some(node) = nodes.get(node_key) else {
+                return (node_key, false);
+            };
+            match node.dir(&key) {
+                NodeDirection::None => {
+                    let node = nodes.remove(node_key).unwrap();
+                    let [left_key, right_key] = node.children;
+                    if nodes.get(left_key).is_none() {
+                        return (right_key, true);
+                    }
+                    if nodes.get(right_key).is_none() {
+                        return (left_key, true);
+                    }
+                    // replace the node with its successor
+                    let (right_key, new_node_key, decreased) = pop_leftmost(nodes, right_key);
+                    let new_node = &mut nodes[new_node_key];
+                    new_node.children = [left_key, right_key];
+                    new_node.longer_side = node.longer_side;
+                    if !decreased {
+                        return (new_node_key, false);
+                    }
+                    // we have reduced the height by one on the right, we need to rebalance
+                    let (new_node_key, increased) =
+                        rebalance(nodes, new_node_key, NodeDirection::Left);
+                    (new_node_key, !increased)
+                }
+                dir => {
+                    let child_key = node.children[dir as usize];
+                    let (child_key, decreased) = aux(nodes, child_key, key);
+                    nodes[node_key].children[dir as usize] = child_key;
+                    if !decreased {
+                        return (node_key, false);
+                    }
+                    // the height has decreased, we need to rebalance
+                    let (node_key, increased) = rebalance(nodes, node_key, !dir);
+                    (node_key, !increased)
+                }
+            }
+        }
+        (self.root, _) = aux(&mut self.nodes, self.root, key);
+        self.check();
+    }
+}
+
+impl<K: Ord> FromIterator<K> for ArenaAvl<K> {
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = K>,
+    {
+        let mut avl = ArenaAvl::new();
+        for x in iter {
+            avl.insert(x);
+        }
+        avl
+    }
+}
+
+// non-consuming iterator
+pub struct IterRef<'a, K> {
+    nodes: &'a Nodes<K>,
+    stack: Vec<(bool, NodeKey)>,
+}
+
+impl<'a, K> Iterator for IterRef<'a, K> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<Self::Item> {
+        let (explored, node_key) = self.stack.pop()?;
+        let Some(node) = self.nodes.get(node_key) else {
+            return self.next();
+        };
+        if explored {
+            self.stack.push((false, node.children[1]));
+            Some(&node.key)
+        } else {
+            self.stack.push((true, node_key));
+            self.stack.push((false, node.children[0]));
+            self.next()
+        }
+    }
+}
+
+impl<'a, K> IntoIterator for &'a ArenaAvl<K> {
+    type Item = &'a K;
+    type IntoIter = IterRef<'a, K>;
+    fn into_iter(self) -> Self::IntoIter {
+        IterRef {
+            nodes: &self.nodes,
+            stack: vec![(false, self.root)],
+        }
+    }
+}
+
+impl<K> ArenaAvl<K> {
+    pub fn iter(&self) -> IterRef<'_, K> {
+        self.into_iter()
+    }
+}
+
+// consuming iterator
+pub struct Iter<K> {
+    nodes: Nodes<K>,
+    stack: Vec<(bool, NodeKey)>,
+}
+
+impl<K> Iterator for Iter<K> {
+    type Item = K;
+    fn next(&mut self) -> Option<Self::Item> {
+        let (explored, node_key) = self.stack.pop()?;
+        if explored {
+            let node = self.nodes.remove(node_key).unwrap();
+            self.stack.push((false, node.children[1]));
+            Some(node.key)
+        } else {
+            let Some(node) = self.nodes.get(node_key) else {
+                return self.next();
+            };
+            let left_key = node.children[0];
+            self.stack.push((true, node_key));
+            self.stack.push((false, left_key));
+            self.next()
+        }
+    }
+}
+
+impl<K> IntoIterator for ArenaAvl<K> {
+    type IntoIter = Iter<K>;
+    type Item = K;
+    fn into_iter(self) -> Self::IntoIter {
+        Iter {
+            nodes: self.nodes,
+            stack: vec![(false, self.root)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::seq::IteratorRandom;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test() {
+        let mut t: super::ArenaAvl<i32> = [1, 2, 3, 4, 5, 6, 7, 8, 15, 14, 13, 12, 11, 10, 9]
+            .iter()
+            .copied()
+            .collect();
+
+        t.remove(8);
+
+        let expected = vec![1, 2, 3, 4, 5, 6, 7, 9, 10, 11, 12, 13, 14, 15];
+
+        let v: Vec<i32> = t.iter().copied().collect();
+        assert_eq!(v, expected);
+
+        let mut v = Vec::new();
+        for &x in &t {
+            v.push(x);
+        }
+        assert_eq!(v, expected);
+
+        let mut v = Vec::new();
+        for x in t {
+            v.push(x);
+        }
+        assert_eq!(v, expected);
+    }
+
+    #[test]
+    fn big_test() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut tree = super::Avl::with_arena();
+        let mut expected = HashSet::new();
+
+        // try to unbalance the tree
+        for x in 0..10000 {
+            tree.insert(x);
+            expected.insert(x);
+        }
+
+        // add some more
+        for _ in 0..10000 {
+            let x: u64 = rng.gen();
+            tree.insert(x);
+            expected.insert(x);
+        }
+        let actual: HashSet<_> = tree.iter().copied().collect();
+        assert_eq!(actual, expected);
+
+        // remove some
+        for _ in 0..1000 {
+            let x: u64 = *expected.iter().choose(&mut rng).unwrap();
+            tree.remove(x);
+            expected.remove(&x);
+        }
+        let actual: HashSet<_> = tree.iter().copied().collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn with_capacity() {
+        let mut tree = super::ArenaAvl::with_capacity(1000);
+        let capacity = tree.capacity();
+        assert!(capacity >= 1000);
+        for x in 0..1000 {
+            tree.insert(x);
+        }
+        assert_eq!(tree.capacity(), capacity);
+        assert!(tree.iter().copied().eq(0..1000));
+    }
+
+    // cargo test --release --features arena bulk_load -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bulk_load() {
+        use std::time::Instant;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let keys: Vec<u64> = (0..1_000_000).map(|_| rng.gen()).collect();
+        // check() is skipped, since it would dominate the timings
+        let start = Instant::now();
+        let mut arena = super::ArenaAvl::new();
+        for &key in &keys {
+            arena.insert_no_check(key);
+        }
+        let arena_time = start.elapsed();
+        let start = Instant::now();
+        let mut boxed = super::Avl::new();
+        for &key in &keys {
+            boxed.insert_no_check(key);
+        }
+        let boxed_time = start.elapsed();
+        println!("1M inserts: ArenaAvl {arena_time:?}, Avl {boxed_time:?}");
+        arena.check();
+        assert!(arena.iter().eq(boxed.iter()));
+    }
+}
@@ -1,18 +1,50 @@
 use std::cmp::Ordering;
+use std::ops::{Add, Bound, RangeBounds};
 
-type Anchor<K> = Option<Box<Node<K>>>;
+use crate::util::{merge_join, range_bounds};
+
+type Anchor<K, S> = Option<Box<Node<K, S>>>;
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
-enum NodeDirection {
+pub(crate) enum NodeDirection {
     Left = 0,
     Right = 1,
     None,
 }
 
-struct Node<K> {
+struct Node<K, S> {
     key: K,
     longer_side: NodeDirection,
-    children: [Anchor<K>; 2],
+    count: usize,
+    summary: S,
+    children: [Anchor<K, S>; 2],
+}
+
+// data about the keys of a subtree, maintained in each node next to the count
+pub trait Summary<K>: Copy + PartialEq {
+    fn of_key(key: &K) -> Self;
+    // summary of two consecutive runs of keys
+    fn combine(self, other: Self) -> Self;
+}
+
+// plain trees keep no summary
+impl<K> Summary<K> for () {
+    fn of_key(_key: &K) -> Self {}
+    fn combine(self, _other: Self) -> Self {}
+}
+
+// sum of the keys of a subtree
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Sum<K>(pub K);
+
+impl<K: Add<Output = K> + Copy + PartialEq> Summary<K> for Sum<K> {
+    fn of_key(key: &K) -> Self {
+        Sum(*key)
+    }
+
+    fn combine(self, other: Self) -> Self {
+        Sum(self.0 + other.0)
+    }
 }
 
 impl std::ops::Not for NodeDirection {
@@ -26,25 +58,76 @@ impl std::ops::Not for NodeDirection {
     }
 }
 
-impl<K> Node<K> {
+impl<K, S: Summary<K>> Node<K, S> {
     fn new(key: K) -> Self {
         Node {
+            summary: S::of_key(&key),
             key,
             longer_side: NodeDirection::None,
+            count: 1,
             children: [None, None],
         }
     }
+
+    // summary of the subtree, from the summaries of the children
+    fn summarize(&self) -> S {
+        let mut summary = S::of_key(&self.key);
+        if let Some(left) = &self.children[0] {
+            summary = left.summary.combine(summary);
+        }
+        if let Some(right) = &self.children[1] {
+            summary = summary.combine(right.summary);
+        }
+        summary
+    }
+
+    // must be called whenever the children change
+    // this also updates the summary
+    fn update_count(&mut self) {
+        self.count = 1 + count(&self.children[0]) + count(&self.children[1]);
+        self.summary = self.summarize();
+    }
 }
 
-fn rotate<K>(anchor: &mut Anchor<K>, dir: NodeDirection) {
+fn count<K, S>(anchor: &Anchor<K, S>) -> usize {
+    anchor.as_ref().map_or(0, |node| node.count)
+}
+
+// follow the longer sides down to a leaf
+fn height<K, S>(mut anchor: &Anchor<K, S>) -> usize {
+    let mut height = 0;
+    while let Some(node) = anchor {
+        height += 1;
+        let dir = match node.longer_side {
+            NodeDirection::Right => NodeDirection::Right,
+            _ => NodeDirection::Left,
+        };
+        anchor = &node.children[dir as usize];
+    }
+    height
+}
+
+// height of the dir-child of a node of the given height
+fn child_height<K, S>(node: &Node<K, S>, height: usize, dir: NodeDirection) -> usize {
+    if node.longer_side == !dir {
+        height - 2
+    } else {
+        height - 1
+    }
+}
+
+fn rotate<K, S: Summary<K>>(anchor: &mut Anchor<K, S>, dir: NodeDirection, rotations: &mut u64) {
     let mut node = anchor.take().unwrap();
     let mut new_root = node.children[!dir as usize].take().unwrap();
     node.children[!dir as usize] = new_root.children[dir as usize].take();
+    node.update_count();
     new_root.children[dir as usize] = Some(node);
+    new_root.update_count();
     *anchor = Some(new_root);
+    *rotations += 1;
 }
 
-impl<K: Ord> Node<K> {
+impl<K: Ord, S> Node<K, S> {
     fn dir(&self, key: &K) -> NodeDirection {
         match key.cmp(&self.key) {
             Ordering::Less => NodeDirection::Left,
@@ -54,25 +137,105 @@ impl<K: Ord> Node<K> {
     }
 }
 
-pub struct Avl<K> {
-    root: Anchor<K>,
+pub struct Avl<K, S = ()> {
+    root: Anchor<K, S>,
+    // number of calls to insert with a key already present
+    #[cfg(feature = "profiling")]
+    rejected_inserts: u64,
+    // number of rotations done by the operations on this tree
+    #[cfg(feature = "profiling")]
+    rotations: u64,
 }
 
 impl<K> Avl<K> {
     pub fn new() -> Self {
-        Avl { root: None }
+        Avl::empty()
+    }
+
+    // the nodes are boxed individually, so there is nothing to allocate upfront
+    // this mirrors ArenaAvl::with_capacity
+    pub fn with_capacity(_capacity: usize) -> Self {
+        Avl::new()
+    }
+
+    // minimum number of keys in an AVL tree of the given height, saturating at usize::MAX
+    pub fn min_keys_for_height(height: usize) -> usize {
+        // the sparsest trees have subtrees of heights h - 1 and h - 2
+        let (mut a, mut b): (usize, usize) = (0, 1);
+        for _ in 0..height {
+            if a == usize::MAX {
+                break;
+            }
+            (a, b) = (b, a.saturating_add(b).saturating_add(1));
+        }
+        a
+    }
+
+    // maximum height of an AVL tree holding n keys
+    pub fn max_height_for_len(n: usize) -> usize {
+        // a and b are the minimum numbers of keys for height and height + 1
+        let (mut a, mut b): (usize, usize) = (0, 1);
+        let mut height = 0;
+        while b <= n {
+            height += 1;
+            // past usize::MAX, no n can reach the next height
+            let Some(next) = a.checked_add(b).and_then(|x| x.checked_add(1)) else {
+                break;
+            };
+            (a, b) = (b, next);
+        }
+        height
+    }
+}
+
+impl<K: Add<Output = K> + Copy + PartialEq> Avl<K, Sum<K>> {
+    // a tree which keeps the sum of each subtree, for range_sum
+    // the sum of all the keys must fit in K: the sums use the + of K, which panics on
+    // overflow when overflow checks are enabled; use Wrapping keys for modular sums
+    pub fn with_sums() -> Self {
+        Avl::empty()
+    }
+}
+
+impl<K, S> Avl<K, S> {
+    fn empty() -> Self {
+        Avl {
+            root: None,
+            #[cfg(feature = "profiling")]
+            rejected_inserts: 0,
+            #[cfg(feature = "profiling")]
+            rotations: 0,
+        }
+    }
+
+    #[cfg(feature = "profiling")]
+    pub fn rejected_inserts(&self) -> u64 {
+        self.rejected_inserts
+    }
+
+    #[cfg(feature = "profiling")]
+    pub fn rotation_count(&self) -> u64 {
+        self.rotations
+    }
+
+    pub fn len(&self) -> usize {
+        count(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
     }
 }
 
-impl<K> Default for Avl<K> {
+impl<K, S> Default for Avl<K, S> {
     fn default() -> Self {
-        Avl::new()
+        Avl::empty()
     }
 }
 
-impl<K: std::fmt::Display> Avl<K> {
+impl<K: std::fmt::Display, S> Avl<K, S> {
     pub fn print(&self) {
-        fn aux<K: std::fmt::Display>(anchor: &Anchor<K>, indent: usize) {
+        fn aux<K: std::fmt::Display, S>(anchor: &Anchor<K, S>, indent: usize) {
             let prefix = "    ".repeat(indent);
             if let Some(node) = anchor {
                 println!("{}- {} ({:?})", prefix, node.key, node.longer_side);
@@ -86,12 +249,16 @@ impl<K: std::fmt::Display> Avl<K> {
     }
 }
 
-impl<K: Ord> Avl<K> {
+impl<K: Ord, S: Summary<K>> Avl<K, S> {
     fn check(&self) {
-        // returns the height
-        fn aux<K: Ord>(anchor: &Anchor<K>, min: Option<&K>, max: Option<&K>) -> i32 {
+        // returns the height and the number of nodes
+        fn aux<K: Ord, S: Summary<K>>(
+            anchor: &Anchor<K, S>,
+            min: Option<&K>,
+            max: Option<&K>,
+        ) -> (i32, usize) {
             let Some(node) = anchor else {
-                return 1;
+                return (1, 0);
             };
             if let Some(min) = min {
                 assert!(node.key > *min);
@@ -99,21 +266,23 @@ impl<K: Ord> Avl<K> {
             if let Some(max) = max {
                 assert!(node.key < *max);
             }
-            let lh = aux(&node.children[0], min, Some(&node.key));
-            let lr = aux(&node.children[1], Some(&node.key), max);
+            let (lh, lc) = aux(&node.children[0], min, Some(&node.key));
+            let (lr, rc) = aux(&node.children[1], Some(&node.key), max);
             match lr - lh {
                 1 => assert_eq!(node.longer_side, NodeDirection::Right),
                 -1 => assert_eq!(node.longer_side, NodeDirection::Left),
                 0 => assert_eq!(node.longer_side, NodeDirection::None),
                 _ => unreachable!(),
             }
-            lh.max(lr) + 1
+            assert_eq!(node.count, lc + rc + 1);
+            assert!(node.summary == node.summarize());
+            (lh.max(lr) + 1, node.count)
         }
         aux(&self.root, None, None);
     }
 
     pub fn contains(&self, key: K) -> bool {
-        fn aux<K: Ord>(anchor: &Anchor<K>, key: K) -> bool {
+        fn aux<K: Ord, S>(anchor: &Anchor<K, S>, key: K) -> bool {
             let Some(node) = anchor else {
                 return false;
             };
@@ -131,7 +300,7 @@ impl<K: Ord> Avl<K> {
     // For a deletion:
     //      - dir is the opposite of the insertion direction
     //      - the return value is true when the height is still decreased after rebalancing
-    fn rebalance(anchor: &mut Anchor<K>, dir: NodeDirection) -> bool {
+    fn rebalance(anchor: &mut Anchor<K, S>, dir: NodeDirection, rotations: &mut u64) -> bool {
         let node = anchor.as_mut().unwrap();
         match node.longer_side {
             // the node was balanced
@@ -158,7 +327,7 @@ impl<K: Ord> Avl<K> {
                         node.longer_side = dir;
                         child.longer_side = !dir;
                         // need a single rotation
-                        rotate(anchor, !dir);
+                        rotate(anchor, !dir, rotations);
                         // this cannot happen during insertion
                         return true;
                     }
@@ -168,7 +337,7 @@ impl<K: Ord> Avl<K> {
                         node.longer_side = NodeDirection::None;
                         child.longer_side = NodeDirection::None;
                         // need a single rotation
-                        rotate(anchor, !dir);
+                        rotate(anchor, !dir, rotations);
                         // the height change is absorbed
                     }
                     // the child is unbalanced in the opposite direction
@@ -193,8 +362,8 @@ impl<K: Ord> Avl<K> {
                         // this will always rebalance the grandchild (the new root)
                         grandchild.longer_side = NodeDirection::None;
                         // need two rotations
-                        rotate(&mut node.children[dir as usize], dir);
-                        rotate(anchor, !dir);
+                        rotate(&mut node.children[dir as usize], dir, rotations);
+                        rotate(anchor, !dir, rotations);
                         // the height change is absorbed
                     }
                 }
@@ -204,8 +373,18 @@ impl<K: Ord> Avl<K> {
     }
 
     pub fn insert(&mut self, key: K) {
+        self.insert_no_check(key);
+        self.check();
+    }
+
+    // insert without validating the whole tree, for benchmarks
+    pub(crate) fn insert_no_check(&mut self, key: K) {
         // returns whether the height has increased
-        fn aux<K: Ord>(anchor: &mut Anchor<K>, key: K) -> bool {
+        fn aux<K: Ord, S: Summary<K>>(
+            anchor: &mut Anchor<K, S>,
+            key: K,
+            rotations: &mut u64,
+        ) -> bool {
             let Some(node) = anchor else {
                 *anchor = Some(Box::new(Node::new(key)));
                 return true;
@@ -213,49 +392,71 @@ impl<K: Ord> Avl<K> {
             match node.dir(&key) {
                 NodeDirection::None => false,
                 dir => {
-                    if !aux(&mut node.children[dir as usize], key) {
+                    let increased = aux(&mut node.children[dir as usize], key, rotations);
+                    node.update_count();
+                    if !increased {
                         return false;
                     }
                     // the height has increased, we need to rebalance
-                    Avl::rebalance(anchor, dir)
+                    Avl::rebalance(anchor, dir, rotations)
                 }
             }
         }
-        aux(&mut self.root, key);
-        self.check();
+        #[cfg(feature = "profiling")]
+        let len = self.len();
+        let mut rotations = 0;
+        aux(&mut self.root, key, &mut rotations);
+        #[cfg(feature = "profiling")]
+        {
+            if self.len() == len {
+                self.rejected_inserts += 1;
+            }
+            self.rotations += rotations;
+        }
     }
 
     pub fn remove(&mut self, key: K) {
         // return the leftmost node and its depth
-        fn leftmost<K: Ord>(mut node: &mut Box<Node<K>>) -> (Box<Node<K>>, usize) {
-            let mut depth = 0;
-            while node.children[0].as_ref().unwrap().children[0].is_some() {
-                node = node.children[0].as_mut().unwrap();
-                depth += 1;
-            }
-            let mut ret = node.children[0].take().unwrap();
-            node.children[0] = ret.children[1].take();
+        fn leftmost<K: Ord, S: Summary<K>>(node: &mut Box<Node<K, S>>) -> (Box<Node<K, S>>, usize) {
+            let left = node.children[0].as_mut().unwrap();
+            let (ret, depth) = if left.children[0].is_some() {
+                let (ret, depth) = leftmost(left);
+                (ret, depth + 1)
+            } else {
+                let mut ret = node.children[0].take().unwrap();
+                node.children[0] = ret.children[1].take();
+                (ret, 0)
+            };
+            node.update_count();
             (ret, depth)
         }
         // returns whether the height has decreased
         // we have replaced the leftmost node with its own right child
         // so we need to know the depth to know where to start from
-        fn leftmost_rebalance<K: Ord>(anchor: &mut Anchor<K>, depth: usize) -> bool {
+        fn leftmost_rebalance<K: Ord, S: Summary<K>>(
+            anchor: &mut Anchor<K, S>,
+            depth: usize,
+            rotations: &mut u64,
+        ) -> bool {
             let node = anchor.as_mut().unwrap();
             if depth > 0 {
-                if !leftmost_rebalance(&mut node.children[0], depth - 1) {
+                if !leftmost_rebalance(&mut node.children[0], depth - 1, rotations) {
                     false
                 } else {
                     // we have reduced the height by one on the left, we need to rebalance
-                    !Avl::rebalance(anchor, NodeDirection::Right)
+                    !Avl::rebalance(anchor, NodeDirection::Right, rotations)
                 }
             } else {
                 // we have reduced the height by one on the left, we need to rebalance
-                !Avl::rebalance(anchor, NodeDirection::Right)
+                !Avl::rebalance(anchor, NodeDirection::Right, rotations)
             }
         }
         // returns whether the height has decreased
-        fn aux<K: Ord>(anchor: &mut Anchor<K>, key: K) -> bool {
+        fn aux<K: Ord, S: Summary<K>>(
+            anchor: &mut Anchor<K, S>,
+            key: K,
+            rotations: &mut u64,
+        ) -> bool {
             let Some(node) = anchor else {
                 return false;
             };
@@ -278,21 +479,24 @@ impl<K: Ord> Avl<K> {
                             None => {
                                 right.children[0] = Some(left);
                                 right.longer_side = node.longer_side;
+                                right.update_count();
                                 *anchor = Some(right);
                                 // we have reduced the height by one on the right, we need to rebalance
-                                !Avl::rebalance(anchor, NodeDirection::Left)
+                                !Avl::rebalance(anchor, NodeDirection::Left, rotations)
                             }
                             Some(_) => {
                                 let (mut new_node, depth) = leftmost(&mut right);
                                 new_node.longer_side = node.longer_side;
                                 new_node.children[0] = Some(left);
                                 new_node.children[1] = Some(right);
+                                new_node.update_count();
                                 // we might need to rebalance some nodes in the right subtree
-                                let ret = leftmost_rebalance(&mut new_node.children[1], depth);
+                                let ret =
+                                    leftmost_rebalance(&mut new_node.children[1], depth, rotations);
                                 *anchor = Some(new_node);
                                 if ret {
                                     // we have reduced the height by one on the right, we need to rebalance
-                                    !Avl::rebalance(anchor, NodeDirection::Left)
+                                    !Avl::rebalance(anchor, NodeDirection::Left, rotations)
                                 } else {
                                     false
                                 }
@@ -301,26 +505,473 @@ impl<K: Ord> Avl<K> {
                     }
                 }
                 dir => {
-                    if !aux(&mut node.children[dir as usize], key) {
+                    let decreased = aux(&mut node.children[dir as usize], key, rotations);
+                    node.update_count();
+                    if !decreased {
                         return false;
                     }
                     // the height has decreased, we need to rebalance
-                    !Avl::rebalance(anchor, !dir)
+                    !Avl::rebalance(anchor, !dir, rotations)
                 }
             }
         }
-        aux(&mut self.root, key);
+        let mut rotations = 0;
+        aux(&mut self.root, key, &mut rotations);
+        #[cfg(feature = "profiling")]
+        {
+            self.rotations += rotations;
+        }
         self.check();
     }
+
+    // remove the leftmost key when dir is Left, the rightmost key when dir is Right
+    fn pop_extreme(&mut self, dir: NodeDirection) -> Option<K> {
+        self.root.as_ref()?;
+        let mut rotations = 0;
+        let (node, _) = detach_extreme(&mut self.root, dir, &mut rotations);
+        #[cfg(feature = "profiling")]
+        {
+            self.rotations += rotations;
+        }
+        Some(node.key)
+    }
+
+    // join two trees, where the keys of left are all lower than the keys of right
+    pub fn concat(mut left: Self, right: Self) -> Self {
+        let left_height = height(&left.root);
+        let right_height = height(&right.root);
+        let mut rotations = 0;
+        (left.root, _) = concat(
+            left.root.take(),
+            left_height,
+            right.root,
+            right_height,
+            &mut rotations,
+        );
+        #[cfg(feature = "profiling")]
+        {
+            left.rotations += right.rotations + rotations;
+        }
+        left.check();
+        left
+    }
+
+    // remove the keys whose positions are in range
+    pub fn remove_rank_range<R: RangeBounds<usize>>(&mut self, range: R) {
+        let (start, end) = range_bounds(range, self.len());
+        let height = height(&self.root);
+        let mut rotations = 0;
+        let (left, left_height, rest, rest_height) =
+            split_rank(self.root.take(), height, start, &mut rotations);
+        let (_, _, right, right_height) =
+            split_rank(rest, rest_height, end - start, &mut rotations);
+        (self.root, _) = concat(left, left_height, right, right_height, &mut rotations);
+        #[cfg(feature = "profiling")]
+        {
+            self.rotations += rotations;
+        }
+        self.check();
+    }
+}
+
+// detach the leftmost node when dir is Left, the rightmost node when dir is Right
+// returns the node and whether the height has decreased
+fn detach_extreme<K: Ord, S: Summary<K>>(
+    anchor: &mut Anchor<K, S>,
+    dir: NodeDirection,
+    rotations: &mut u64,
+) -> (Box<Node<K, S>>, bool) {
+    let node = anchor.as_mut().unwrap();
+    if node.children[dir as usize].is_none() {
+        let mut node = anchor.take().unwrap();
+        *anchor = node.children[!dir as usize].take();
+        node.update_count();
+        return (node, true);
+    }
+    let (ret, decreased) = detach_extreme(&mut node.children[dir as usize], dir, rotations);
+    node.update_count();
+    if !decreased {
+        return (ret, false);
+    }
+    // the height has decreased, we need to rebalance
+    (ret, !Avl::rebalance(anchor, !dir, rotations))
 }
 
-impl<K: Ord> FromIterator<K> for Avl<K> {
+// attach pivot and other along the dir spine of anchor, where the keys of other are on
+// the dir side of pivot, and pivot on the dir side of anchor
+// anchor must not be shorter than other
+// returns whether the height of anchor has increased
+fn join_spine<K: Ord, S: Summary<K>>(
+    anchor: &mut Anchor<K, S>,
+    height: usize,
+    mut pivot: Box<Node<K, S>>,
+    other: Anchor<K, S>,
+    other_height: usize,
+    dir: NodeDirection,
+    rotations: &mut u64,
+) -> bool {
+    if height <= other_height + 1 {
+        // the heights are close enough, pivot becomes the root of the subtree
+        pivot.longer_side = if height > other_height {
+            !dir
+        } else {
+            NodeDirection::None
+        };
+        pivot.children[!dir as usize] = anchor.take();
+        pivot.children[dir as usize] = other;
+        pivot.update_count();
+        *anchor = Some(pivot);
+        return true;
+    }
+    let node = anchor.as_mut().unwrap();
+    let height = child_height(node, height, dir);
+    let increased = join_spine(
+        &mut node.children[dir as usize],
+        height,
+        pivot,
+        other,
+        other_height,
+        dir,
+        rotations,
+    );
+    node.update_count();
+    if !increased {
+        return false;
+    }
+    // the height has increased, we need to rebalance
+    Avl::rebalance(anchor, dir, rotations)
+}
+
+// join two subtrees and a node whose key is between them
+// returns the joined subtree and its height
+fn join<K: Ord, S: Summary<K>>(
+    mut left: Anchor<K, S>,
+    left_height: usize,
+    pivot: Box<Node<K, S>>,
+    mut right: Anchor<K, S>,
+    right_height: usize,
+    rotations: &mut u64,
+) -> (Anchor<K, S>, usize) {
+    if left_height >= right_height {
+        let increased = join_spine(
+            &mut left,
+            left_height,
+            pivot,
+            right,
+            right_height,
+            NodeDirection::Right,
+            rotations,
+        );
+        (left, left_height + increased as usize)
+    } else {
+        let increased = join_spine(
+            &mut right,
+            right_height,
+            pivot,
+            left,
+            left_height,
+            NodeDirection::Left,
+            rotations,
+        );
+        (right, right_height + increased as usize)
+    }
+}
+
+// join two subtrees, where the keys of left are all lower than the keys of right
+// returns the joined subtree and its height
+fn concat<K: Ord, S: Summary<K>>(
+    left: Anchor<K, S>,
+    left_height: usize,
+    mut right: Anchor<K, S>,
+    right_height: usize,
+    rotations: &mut u64,
+) -> (Anchor<K, S>, usize) {
+    if right.is_none() {
+        return (left, left_height);
+    }
+    let (pivot, decreased) = detach_extreme(&mut right, NodeDirection::Left, rotations);
+    join(
+        left,
+        left_height,
+        pivot,
+        right,
+        right_height - decreased as usize,
+        rotations,
+    )
+}
+
+// split a subtree of the given height into its k leftmost nodes and the rest
+// returns the two subtrees with their heights
+fn split_rank<K: Ord, S: Summary<K>>(
+    anchor: Anchor<K, S>,
+    height: usize,
+    k: usize,
+    rotations: &mut u64,
+) -> (Anchor<K, S>, usize, Anchor<K, S>, usize) {
+    let Some(mut node) = anchor else {
+        return (None, 0, None, 0);
+    };
+    let left_height = child_height(&node, height, NodeDirection::Left);
+    let right_height = child_height(&node, height, NodeDirection::Right);
+    let [left, right] = std::mem::take(&mut node.children);
+    let left_count = count(&left);
+    if k <= left_count {
+        let (left, left_height, middle, middle_height) =
+            split_rank(left, left_height, k, rotations);
+        let (right, right_height) =
+            join(middle, middle_height, node, right, right_height, rotations);
+        (left, left_height, right, right_height)
+    } else {
+        let (middle, middle_height, right, right_height) =
+            split_rank(right, right_height, k - left_count - 1, rotations);
+        let (left, left_height) = join(left, left_height, node, middle, middle_height, rotations);
+        (left, left_height, right, right_height)
+    }
+}
+
+impl<K: Ord, S: Summary<K>> Avl<K, S> {
+    // remove the n smallest keys, returned in increasing order
+    pub fn pop_min_n(&mut self, n: usize) -> Vec<K> {
+        let mut ret = Vec::new();
+        while ret.len() < n {
+            let Some(key) = self.pop_extreme(NodeDirection::Left) else {
+                break;
+            };
+            ret.push(key);
+        }
+        self.check();
+        ret
+    }
+
+    // remove the n largest keys, returned in decreasing order
+    pub fn pop_max_n(&mut self, n: usize) -> Vec<K> {
+        let mut ret = Vec::new();
+        while ret.len() < n {
+            let Some(key) = self.pop_extreme(NodeDirection::Right) else {
+                break;
+            };
+            ret.push(key);
+        }
+        self.check();
+        ret
+    }
+
+    pub fn is_subset(&self, other: &Self) -> bool {
+        let mut others = other.iter().peekable();
+        for key in self {
+            // skip the keys of other which are not in self
+            while others.next_if(|other_key| *other_key < key).is_some() {}
+            if others.next_if_eq(&key).is_none() {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn is_superset(&self, other: &Self) -> bool {
+        other.is_subset(self)
+    }
+
+    // walk both trees in order, pairing equal keys
+    pub fn merge_join<'a>(
+        &'a self,
+        other: &'a Self,
+    ) -> impl Iterator<Item = (Option<&'a K>, Option<&'a K>)> {
+        merge_join(self.iter().peekable(), other.iter().peekable())
+    }
+}
+
+impl<K: Ord> Avl<K> {
+    // build a balanced tree in O(n) from keys in increasing order
+    pub fn from_sorted<I: IntoIterator<Item = K>>(keys: I) -> Self {
+        Avl::build_sorted(keys)
+    }
+}
+
+impl<K: Ord, S: Summary<K>> Avl<K, S> {
+    // from_sorted, for any summary
+    fn build_sorted<I: IntoIterator<Item = K>>(keys: I) -> Self {
+        // returns the subtree of the n next keys and its height
+        fn aux<K, S: Summary<K>>(
+            keys: &mut impl Iterator<Item = K>,
+            n: usize,
+        ) -> (Anchor<K, S>, usize) {
+            if n == 0 {
+                return (None, 0);
+            }
+            // the left side gets the extra key, if any
+            let (left, left_height) = aux(keys, n / 2);
+            let mut node = Box::new(Node::new(keys.next().unwrap()));
+            let (right, right_height) = aux(keys, (n - 1) / 2);
+            node.longer_side = if left_height > right_height {
+                NodeDirection::Left
+            } else {
+                NodeDirection::None
+            };
+            node.children = [left, right];
+            node.update_count();
+            (Some(node), left_height + 1)
+        }
+        let keys: Vec<K> = keys.into_iter().collect();
+        let n = keys.len();
+        let (root, _) = aux(&mut keys.into_iter(), n);
+        let mut avl = Avl::empty();
+        avl.root = root;
+        avl.check();
+        avl
+    }
+
+    // position of key in increasing order
+    pub fn index_of(&self, key: &K) -> Option<usize> {
+        let mut anchor = &self.root;
+        // number of keys lower than the current subtree
+        let mut offset = 0;
+        while let Some(node) = anchor {
+            match key.cmp(&node.key) {
+                Ordering::Less => anchor = &node.children[0],
+                Ordering::Greater => {
+                    offset += count(&node.children[0]) + 1;
+                    anchor = &node.children[1];
+                }
+                Ordering::Equal => return Some(offset + count(&node.children[0])),
+            }
+        }
+        None
+    }
+
+    // the key at position index in increasing order
+    pub fn select(&self, mut index: usize) -> Option<&K> {
+        let mut anchor = &self.root;
+        while let Some(node) = anchor {
+            let left_count = count(&node.children[0]);
+            match index.cmp(&left_count) {
+                Ordering::Less => anchor = &node.children[0],
+                Ordering::Greater => {
+                    index -= left_count + 1;
+                    anchor = &node.children[1];
+                }
+                Ordering::Equal => return Some(&node.key),
+            }
+        }
+        None
+    }
+
+    // keys of self which are not in other, as a new balanced tree
+    pub fn difference_tree(&self, other: &Self) -> Self
+    where
+        K: Clone,
+    {
+        let keys = self.merge_join(other).filter_map(|pair| match pair {
+            (Some(key), None) => Some(key.clone()),
+            _ => None,
+        });
+        Avl::build_sorted(keys)
+    }
+
+    // keys in range, in increasing order
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> impl Iterator<Item = &K> {
+        // start from the first key not below range
+        let mut stack = Vec::new();
+        let mut anchor = &self.root;
+        while let Some(node) = anchor {
+            let above_start = match range.start_bound() {
+                Bound::Included(start) => node.key >= *start,
+                Bound::Excluded(start) => node.key > *start,
+                Bound::Unbounded => true,
+            };
+            if above_start {
+                stack.push((true, anchor));
+                anchor = &node.children[0];
+            } else {
+                anchor = &node.children[1];
+            }
+        }
+        IterRef { stack }.take_while(move |key| range.contains(*key))
+    }
+
+    // copy the keys in range into a new balanced tree
+    pub fn subtree_range<R: RangeBounds<K>>(&self, range: R) -> Self
+    where
+        K: Clone,
+    {
+        Avl::build_sorted(self.range(range).cloned())
+    }
+}
+
+impl<K: Ord + Add<Output = K> + Default + Copy> Avl<K, Sum<K>> {
+    // sum of the keys in range, in O(log n)
+    pub fn range_sum<R: RangeBounds<K>>(&self, range: R) -> K {
+        let above_start = |key: &K| match range.start_bound() {
+            Bound::Included(start) => key >= start,
+            Bound::Excluded(start) => key > start,
+            Bound::Unbounded => true,
+        };
+        let below_end = |key: &K| match range.end_bound() {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+        let sum = |anchor: &Anchor<K, Sum<K>>| {
+            anchor.as_ref().map_or(K::default(), |node| node.summary.0)
+        };
+        // find where the searches for both ends split, the highest node in range
+        let mut anchor = &self.root;
+        let top = loop {
+            let Some(node) = anchor else {
+                return K::default();
+            };
+            if !above_start(&node.key) {
+                anchor = &node.children[1];
+            } else if !below_end(&node.key) {
+                anchor = &node.children[0];
+            } else {
+                break node;
+            }
+        };
+        let mut total = top.key;
+        // on the left, take the nodes not below range with their right subtrees
+        let mut anchor = &top.children[0];
+        while let Some(node) = anchor {
+            if above_start(&node.key) {
+                total = total + node.key + sum(&node.children[1]);
+                anchor = &node.children[0];
+            } else {
+                anchor = &node.children[1];
+            }
+        }
+        // on the right, take the nodes not above range with their left subtrees
+        let mut anchor = &top.children[1];
+        while let Some(node) = anchor {
+            if below_end(&node.key) {
+                total = total + sum(&node.children[0]) + node.key;
+                anchor = &node.children[1];
+            } else {
+                anchor = &node.children[0];
+            }
+        }
+        total
+    }
+}
+
+impl<K: Ord, S: Summary<K>> FromIterator<K> for Avl<K, S> {
     fn from_iter<T>(iter: T) -> Self
     where
         T: IntoIterator<Item = K>,
     {
-        let mut avl = Avl::new();
-        for x in iter {
+        // collect the keys while they are strictly increasing
+        let mut iter = iter.into_iter();
+        let mut sorted: Vec<K> = Vec::new();
+        let mut rest = None;
+        for x in iter.by_ref() {
+            if sorted.last().is_some_and(|last| x <= *last) {
+                rest = Some(x);
+                break;
+            }
+            sorted.push(x);
+        }
+        let mut avl = Avl::build_sorted(sorted);
+        // fall back to inserting the remaining keys one at a time
+        for x in rest.into_iter().chain(iter) {
             avl.insert(x);
         }
         avl
@@ -328,11 +979,11 @@ impl<K: Ord> FromIterator<K> for Avl<K> {
 }
 
 // non-consuming iterator
-pub struct IterRef<'a, K> {
-    stack: Vec<(bool, &'a Anchor<K>)>,
+pub struct IterRef<'a, K, S = ()> {
+    stack: Vec<(bool, &'a Anchor<K, S>)>,
 }
 
-impl<'a, K> Iterator for IterRef<'a, K> {
+impl<'a, K, S> Iterator for IterRef<'a, K, S> {
     type Item = &'a K;
     fn next(&mut self) -> Option<Self::Item> {
         let stack = &mut self.stack;
@@ -351,9 +1002,9 @@ impl<'a, K> Iterator for IterRef<'a, K> {
     }
 }
 
-impl<'a, K> IntoIterator for &'a Avl<K> {
+impl<'a, K, S> IntoIterator for &'a Avl<K, S> {
     type Item = &'a K;
-    type IntoIter = IterRef<'a, K>;
+    type IntoIter = IterRef<'a, K, S>;
     fn into_iter(self) -> Self::IntoIter {
         IterRef {
             stack: vec![(false, &self.root)],
@@ -361,19 +1012,126 @@ impl<'a, K> IntoIterator for &'a Avl<K> {
     }
 }
 
-impl<K> Avl<K> {
-    pub fn iter(&self) -> IterRef<K> {
+impl<K, S> Avl<K, S> {
+    pub fn iter(&self) -> IterRef<'_, K, S> {
         self.into_iter()
     }
+
+    // a copy of the keys in increasing order, which does not borrow the tree
+    pub fn to_sorted_vec(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        self.iter().cloned().collect()
+    }
+
+    pub fn first_cursor(&self) -> Cursor<'_, K, S> {
+        let mut cursor = Cursor {
+            root: &self.root,
+            stack: Vec::new(),
+        };
+        cursor.descend(&self.root, NodeDirection::Left);
+        cursor
+    }
+
+    pub fn last_cursor(&self) -> Cursor<'_, K, S> {
+        let mut cursor = Cursor {
+            root: &self.root,
+            stack: Vec::new(),
+        };
+        cursor.descend(&self.root, NodeDirection::Right);
+        cursor
+    }
+
+    // cursor at the first key greater than or equal to key, or past the end if there is none
+    pub fn lower_bound_cursor(&self, key: &K) -> Cursor<'_, K, S>
+    where
+        K: Ord,
+    {
+        let mut cursor = Cursor {
+            root: &self.root,
+            stack: Vec::new(),
+        };
+        // keep the whole path, then cut it at the last node that was not too small
+        let mut len = 0;
+        let mut anchor = &self.root;
+        while let Some(node) = anchor {
+            cursor.stack.push(node);
+            let dir = match key.cmp(&node.key) {
+                Ordering::Greater => NodeDirection::Right,
+                Ordering::Equal => break,
+                Ordering::Less => NodeDirection::Left,
+            };
+            if dir == NodeDirection::Left {
+                len = cursor.stack.len();
+            }
+            anchor = &node.children[dir as usize];
+        }
+        if anchor.is_none() {
+            cursor.stack.truncate(len);
+        }
+        cursor
+    }
+}
+
+// bidirectional cursor
+// it borrows the tree, so any structural mutation requires dropping it first
+pub struct Cursor<'a, K, S = ()> {
+    root: &'a Anchor<K, S>,
+    // path from the root to the current node, empty when past either end
+    stack: Vec<&'a Node<K, S>>,
+}
+
+impl<'a, K, S> Cursor<'a, K, S> {
+    pub fn key(&self) -> Option<&'a K> {
+        self.stack.last().map(|node| &node.key)
+    }
+
+    pub fn move_next(&mut self) {
+        self.step(NodeDirection::Right);
+    }
+
+    pub fn move_prev(&mut self) {
+        self.step(NodeDirection::Left);
+    }
+
+    // follow the dir-children from anchor
+    fn descend(&mut self, mut anchor: &'a Anchor<K, S>, dir: NodeDirection) {
+        while let Some(node) = anchor {
+            self.stack.push(node);
+            anchor = &node.children[dir as usize];
+        }
+    }
+
+    fn step(&mut self, dir: NodeDirection) {
+        let Some(&node) = self.stack.last() else {
+            // past one end, wrap around to the other end
+            self.descend(self.root, !dir);
+            return;
+        };
+        if node.children[dir as usize].is_some() {
+            self.descend(&node.children[dir as usize], !dir);
+            return;
+        }
+        // go up until we come from a !dir-child
+        while let Some(child) = self.stack.pop() {
+            if let Some(parent) = self.stack.last() {
+                let from = parent.children[!dir as usize].as_deref();
+                if from.is_some_and(|from| std::ptr::eq(from, child)) {
+                    return;
+                }
+            }
+        }
+    }
 }
 
 // consuming iterator
 
-pub struct Iter<K> {
-    stack: Vec<Anchor<K>>,
+pub struct Iter<K, S = ()> {
+    stack: Vec<Anchor<K, S>>,
 }
 
-impl<K> Iterator for Iter<K> {
+impl<K, S> Iterator for Iter<K, S> {
     type Item = K;
     fn next(&mut self) -> Option<Self::Item> {
         let stack = &mut self.stack;
@@ -391,8 +1149,8 @@ impl<K> Iterator for Iter<K> {
     }
 }
 
-impl<K> IntoIterator for Avl<K> {
-    type IntoIter = Iter<K>;
+impl<K, S> IntoIterator for Avl<K, S> {
+    type IntoIter = Iter<K, S>;
     type Item = K;
     fn into_iter(self) -> Self::IntoIter {
         Iter {
@@ -401,6 +1159,13 @@ impl<K> IntoIterator for Avl<K> {
     }
 }
 
+impl<K, S> Avl<K, S> {
+    // append the keys in order, reusing the allocation of buf
+    pub fn flatten_into(self, buf: &mut Vec<K>) {
+        buf.extend(self);
+    }
+}
+
 #[test]
 fn test() {
     let mut t: Avl<i32> = [1, 2, 3, 4, 5, 6, 7, 8, 15, 14, 13, 12, 11, 10, 9]
@@ -464,4 +1229,376 @@ mod tests {
         let actual: HashSet<_> = tree.iter().copied().collect();
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn pop_min_max_n() {
+        let mut tree: super::Avl<i32> = (0..100).collect();
+        assert_eq!(tree.pop_min_n(3), vec![0, 1, 2]);
+        assert_eq!(tree.pop_max_n(3), vec![99, 98, 97]);
+        assert_eq!(tree.pop_min_n(0), vec![]);
+        tree.check();
+        let rest: Vec<i32> = tree.iter().copied().collect();
+        assert_eq!(rest, (3..97).collect::<Vec<_>>());
+
+        // asking for more than the tree holds empties it
+        assert_eq!(tree.pop_max_n(1000), (3..97).rev().collect::<Vec<_>>());
+        assert_eq!(tree.pop_min_n(1), vec![]);
+        assert!(tree.iter().next().is_none());
+    }
+
+    #[test]
+    fn subset() {
+        let empty = super::Avl::new();
+        let a: super::Avl<i32> = [1, 2].into_iter().collect();
+        let b: super::Avl<i32> = [1, 2, 3].into_iter().collect();
+        let c: super::Avl<i32> = [1, 3].into_iter().collect();
+        assert!(a.is_subset(&b));
+        assert!(b.is_superset(&a));
+        assert!(!a.is_subset(&c));
+        assert!(!b.is_subset(&a));
+        assert!(a.is_subset(&a));
+        assert!(empty.is_subset(&a));
+        assert!(!a.is_subset(&empty));
+    }
+
+    #[test]
+    fn cursor() {
+        let tree: super::Avl<i32> = (0..100).collect();
+        let mut cursor = tree.first_cursor();
+        let mut forward = Vec::new();
+        while let Some(&key) = cursor.key() {
+            forward.push(key);
+            cursor.move_next();
+        }
+        assert_eq!(forward, (0..100).collect::<Vec<_>>());
+
+        // from past the end, walk back
+        let mut backward = Vec::new();
+        cursor.move_prev();
+        while let Some(&key) = cursor.key() {
+            backward.push(key);
+            cursor.move_prev();
+        }
+        backward.reverse();
+        assert_eq!(backward, forward);
+
+        let mut cursor = tree.last_cursor();
+        assert_eq!(cursor.key(), Some(&99));
+        cursor.move_prev();
+        cursor.move_prev();
+        assert_eq!(cursor.key(), Some(&97));
+        cursor.move_next();
+        assert_eq!(cursor.key(), Some(&98));
+
+        let empty = super::Avl::<i32>::new();
+        let mut cursor = empty.first_cursor();
+        assert_eq!(cursor.key(), None);
+        cursor.move_next();
+        assert_eq!(cursor.key(), None);
+        assert_eq!(empty.lower_bound_cursor(&0).key(), None);
+    }
+
+    #[test]
+    fn lower_bound_cursor() {
+        let tree: super::Avl<i32> = (0..100).map(|x| 2 * x).collect();
+        for key in -1..=200 {
+            let cursor = tree.lower_bound_cursor(&key);
+            let expected = (0..100).map(|x| 2 * x).find(|&x| x >= key);
+            assert_eq!(cursor.key().copied(), expected);
+        }
+
+        // from the middle, walk both ways
+        let mut cursor = tree.lower_bound_cursor(&101);
+        assert_eq!(cursor.key(), Some(&102));
+        let mut forward = Vec::new();
+        while let Some(&key) = cursor.key() {
+            forward.push(key);
+            cursor.move_next();
+        }
+        assert_eq!(forward, (51..100).map(|x| 2 * x).collect::<Vec<_>>());
+        let mut cursor = tree.lower_bound_cursor(&100);
+        assert_eq!(cursor.key(), Some(&100));
+        let mut backward = Vec::new();
+        while let Some(&key) = cursor.key() {
+            backward.push(key);
+            cursor.move_prev();
+        }
+        assert_eq!(backward, (0..=50).rev().map(|x| 2 * x).collect::<Vec<_>>());
+
+        // past the end, walk back
+        let mut cursor = tree.lower_bound_cursor(&199);
+        assert_eq!(cursor.key(), None);
+        cursor.move_prev();
+        assert_eq!(cursor.key(), Some(&198));
+    }
+
+    #[test]
+    fn merge_join() {
+        let a: super::Avl<i32> = [1, 2, 4].into_iter().collect();
+        let b: super::Avl<i32> = [2, 3, 4].into_iter().collect();
+        let actual: Vec<_> = a.merge_join(&b).collect();
+        let expected = vec![
+            (Some(&1), None),
+            (Some(&2), Some(&2)),
+            (None, Some(&3)),
+            (Some(&4), Some(&4)),
+        ];
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn flatten_into() {
+        let a: super::Avl<i32> = (0..50).rev().collect();
+        let b: super::Avl<i32> = (50..100).collect();
+        let mut buf = Vec::with_capacity(100);
+        let capacity = buf.capacity();
+        a.flatten_into(&mut buf);
+        b.flatten_into(&mut buf);
+        assert_eq!(buf, (0..100).collect::<Vec<_>>());
+        assert_eq!(buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn test_remove_rank_range() {
+        let mut avl: super::Avl<i32> = (0..1000).collect();
+        assert_eq!(avl.len(), 1000);
+        avl.remove_rank_range(100..200);
+        assert_eq!(avl.len(), 900);
+        assert!(avl.iter().copied().eq((0..100).chain(200..1000)));
+        avl.remove_rank_range(..50);
+        avl.remove_rank_range(800..);
+        assert_eq!(avl.len(), 800);
+        assert!(avl.iter().copied().eq((50..100).chain(200..950)));
+        avl.remove_rank_range(..);
+        assert!(avl.is_empty());
+    }
+
+    #[test]
+    fn subtree_range() {
+        let avl: super::Avl<i32> = (0..1000).collect();
+        let sub = avl.subtree_range(100..200);
+        sub.check();
+        assert_eq!(sub.len(), 100);
+        assert!(sub.iter().copied().eq(100..200));
+        assert!(super::height(&sub.root) <= 8);
+        assert!(avl.subtree_range(..=0).iter().eq([&0]));
+        assert!(avl.subtree_range(990..).iter().copied().eq(990..1000));
+        assert!(avl.subtree_range(2000..).is_empty());
+        for n in 0..20 {
+            let avl = super::Avl::from_sorted(0..n);
+            assert!(avl.iter().copied().eq(0..n));
+        }
+    }
+
+    #[test]
+    fn height_bounds() {
+        type Avl = super::Avl<i32>;
+        let expected = [0, 1, 2, 4, 7, 12, 20, 33];
+        for (height, &n) in expected.iter().enumerate() {
+            assert_eq!(Avl::min_keys_for_height(height), n);
+            assert_eq!(Avl::max_height_for_len(n), height);
+        }
+        assert_eq!(Avl::max_height_for_len(3), 2);
+        assert_eq!(Avl::max_height_for_len(1000), 14);
+        let avl: Avl = (0..1000).collect();
+        assert!(super::height(&avl.root) <= Avl::max_height_for_len(1000));
+
+        // large n must not overflow
+        let height = Avl::max_height_for_len(usize::MAX);
+        let n = Avl::min_keys_for_height(height);
+        assert!(n < usize::MAX);
+        assert_eq!(Avl::max_height_for_len(n), height);
+        assert_eq!(Avl::max_height_for_len(n - 1), height - 1);
+        assert_eq!(Avl::min_keys_for_height(height + 1), usize::MAX);
+        assert_eq!(Avl::min_keys_for_height(usize::MAX), usize::MAX);
+    }
+
+    #[test]
+    fn from_iter_sorted() {
+        use rand::seq::SliceRandom;
+        let avl: super::Avl<i32> = (0..10000).collect();
+        avl.check();
+        assert_eq!(avl.len(), 10000);
+        assert_eq!(super::height(&avl.root), 14);
+        assert!(avl.iter().copied().eq(0..10000));
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut values: Vec<i32> = (0..1000).collect();
+        values.shuffle(&mut rng);
+        values.extend(500..600);
+        let avl: super::Avl<i32> = values.into_iter().collect();
+        avl.check();
+        assert!(avl.iter().copied().eq(0..1000));
+    }
+
+    #[test]
+    fn difference_tree() {
+        let a: super::Avl<i32> = [1, 2, 3, 4].into_iter().collect();
+        let b: super::Avl<i32> = [2, 4, 5].into_iter().collect();
+        let diff = a.difference_tree(&b);
+        diff.check();
+        assert!(diff.iter().eq([&1, &3]));
+        let a: super::Avl<i32> = (0..1000).collect();
+        let b: super::Avl<i32> = (0..1000).filter(|x| x % 3 == 0).collect();
+        let diff = a.difference_tree(&b);
+        diff.check();
+        assert!(diff.iter().copied().eq((0..1000).filter(|x| x % 3 != 0)));
+        assert!(b.difference_tree(&a).is_empty());
+    }
+
+    #[test]
+    fn index_of() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut tree = super::Avl::new();
+        for _ in 0..1000 {
+            tree.insert(rng.gen_range(0..10000));
+        }
+        for (index, key) in tree.iter().enumerate() {
+            assert_eq!(tree.index_of(key), Some(index));
+            assert_eq!(tree.select(index), Some(key));
+        }
+        assert_eq!(tree.select(tree.len()), None);
+        assert_eq!(tree.index_of(&10000), None);
+    }
+
+    #[test]
+    fn to_sorted_vec() {
+        let mut tree: super::Avl<i32> = [5, 1, 4, 2, 3].into_iter().collect();
+        let snapshot = tree.to_sorted_vec();
+        tree.insert(0);
+        tree.remove(3);
+        assert_eq!(snapshot, vec![1, 2, 3, 4, 5]);
+        assert_eq!(tree.to_sorted_vec(), vec![0, 1, 2, 4, 5]);
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn rejected_inserts() {
+        let mut tree = super::Avl::new();
+        for x in [5, 4, 2, 5, 3, 9, 4, 5, 8] {
+            tree.insert(x);
+        }
+        assert_eq!(tree.rejected_inserts(), 3);
+        assert_eq!(tree.len(), 6);
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn rotation_count() {
+        let mut tree: super::Avl<i32> = super::Avl::from_sorted(0..1000);
+        assert_eq!(tree.rotation_count(), 0);
+        // detaching, joining and splitting rotate too, and count for the tree
+        tree.pop_min_n(300);
+        let popped = tree.rotation_count();
+        assert!(popped > 0);
+        for x in 1000..1100 {
+            tree = super::Avl::concat(tree, super::Avl::from_sorted([x]));
+        }
+        let joined = tree.rotation_count();
+        assert!(joined > popped);
+        for _ in 0..100 {
+            tree.remove_rank_range(0..1);
+        }
+        assert!(tree.rotation_count() > joined);
+    }
+
+    #[test]
+    fn concat() {
+        for (n, k) in [
+            (0, 0),
+            (10, 0),
+            (10, 10),
+            (1000, 1),
+            (1000, 500),
+            (1000, 999),
+        ] {
+            let tree: super::Avl<i32> = (0..n).collect();
+            let height = super::height(&tree.root);
+            let (left, _, right, _) = super::split_rank(tree.root, height, k, &mut 0);
+            let mut left_tree = super::Avl::new();
+            left_tree.root = left;
+            let mut right_tree = super::Avl::new();
+            right_tree.root = right;
+            assert_eq!(left_tree.len(), k);
+            let tree = super::Avl::concat(left_tree, right_tree);
+            assert!(tree.iter().copied().eq(0..n));
+        }
+        let left: super::Avl<i32> = (0..10).collect();
+        let right: super::Avl<i32> = (10..1000).collect();
+        let tree = super::Avl::concat(left, right);
+        assert_eq!(tree.len(), 1000);
+        assert!(tree.iter().copied().eq(0..1000));
+    }
+
+    #[test]
+    fn range_sum() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut tree = super::Avl::with_sums();
+        for _ in 0..1000 {
+            tree.insert(rng.gen_range(0..10000u64));
+        }
+        let check = |tree: &super::Avl<u64, super::Sum<u64>>, rng: &mut rand::rngs::StdRng| {
+            for _ in 0..100 {
+                let a = rng.gen_range(0..10000);
+                let b = rng.gen_range(a..10000);
+                let expected: u64 = tree.range(a..b).sum();
+                assert_eq!(tree.range_sum(a..b), expected);
+                let expected: u64 = tree.range(a..=b).sum();
+                assert_eq!(tree.range_sum(a..=b), expected);
+                let expected: u64 = tree.range(a..).sum();
+                assert_eq!(tree.range_sum(a..), expected);
+            }
+            assert_eq!(tree.range_sum(..), tree.iter().sum::<u64>());
+        };
+        check(&tree, &mut rng);
+
+        // the sums are kept through removals, splits and joins, which check() validates
+        for _ in 0..200 {
+            tree.remove(rng.gen_range(0..10000));
+        }
+        tree.pop_min_n(50);
+        tree.remove_rank_range(100..300);
+        check(&tree, &mut rng);
+        let other: super::Avl<u64, super::Sum<u64>> = (10000..10500).collect();
+        let tree = super::Avl::concat(tree, other);
+        check(&tree, &mut rng);
+        assert_eq!(tree.range_sum(10000..), (10000..10500).sum());
+
+        assert_eq!(
+            super::Avl::<u64, super::Sum<u64>>::with_sums().range_sum(..),
+            0
+        );
+        assert_eq!(tree.range_sum(10500..), 0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "overflow")]
+    fn range_sum_overflow() {
+        let mut tree = super::Avl::with_sums();
+        tree.insert(u64::MAX);
+        tree.insert(1);
+    }
+
+    #[test]
+    fn range_sum_wrapping() {
+        use std::num::Wrapping;
+        let mut tree = super::Avl::with_sums();
+        for x in [u64::MAX, 1, 2, u64::MAX - 1] {
+            tree.insert(Wrapping(x));
+        }
+        assert_eq!(tree.range_sum(..), Wrapping(0));
+        assert_eq!(tree.range_sum(Wrapping(2)..), Wrapping(u64::MAX));
+        assert_eq!(tree.range_sum(..Wrapping(3)), Wrapping(3));
+    }
+
+    #[test]
+    fn with_capacity() {
+        let mut tree = super::Avl::with_capacity(1000);
+        assert!(tree.is_empty());
+        for x in 0..1000 {
+            tree.insert(x);
+        }
+        assert!(tree.iter().copied().eq(0..1000));
+    }
 }
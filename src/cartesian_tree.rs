@@ -0,0 +1,161 @@
+// Cartesian tree of an array: the minimum is at the root, and the in-order traversal gives
+// back the array, just like a treap whose priorities are the values (reversed)
+// the minimum of a range of positions is then the lowest common ancestor of its ends
+pub struct CartesianTree<V> {
+    values: Vec<V>,
+    depths: Vec<usize>,
+    // ancestors[j][i] is the 2^j-th ancestor of i, or the root when there are not enough
+    ancestors: Vec<Vec<usize>>,
+}
+
+impl<V: Ord + Clone> CartesianTree<V> {
+    // the tree itself is built in O(n) with a monotonic stack, but the binary lifting tables
+    // take O(n log n) time and space, so this is O(n log n) overall
+    pub fn new(values: &[V]) -> Self {
+        let n = values.len();
+        // the right spine of the tree built so far, with a monotonic stack
+        let mut parents: Vec<usize> = (0..n).collect();
+        let mut stack: Vec<usize> = Vec::new();
+        for index in 0..n {
+            let mut last = None;
+            // on ties, the leftmost stays above
+            while let Some(&top) = stack.last() {
+                if values[top] <= values[index] {
+                    break;
+                }
+                stack.pop();
+                last = Some(top);
+            }
+            if let Some(last) = last {
+                parents[last] = index;
+            }
+            if let Some(&top) = stack.last() {
+                parents[index] = top;
+            }
+            stack.push(index);
+        }
+        // the root is the bottom of the stack, and is its own parent
+        if let Some(&root) = stack.first() {
+            parents[root] = root;
+        }
+
+        // compute the depths, from the root down
+        let mut children = vec![Vec::new(); n];
+        for (index, &parent) in parents.iter().enumerate() {
+            if parent != index {
+                children[parent].push(index);
+            }
+        }
+        let mut depths = vec![0; n];
+        let mut queue: Vec<usize> = stack.first().copied().into_iter().collect();
+        while let Some(index) = queue.pop() {
+            for &child in &children[index] {
+                depths[child] = depths[index] + 1;
+                queue.push(child);
+            }
+        }
+
+        // binary lifting
+        let mut ancestors = vec![parents];
+        while 1 << ancestors.len() < n {
+            let last = ancestors.last().unwrap();
+            let next = last.iter().map(|&ancestor| last[ancestor]).collect();
+            ancestors.push(next);
+        }
+
+        let tree = CartesianTree {
+            values: values.to_vec(),
+            depths,
+            ancestors,
+        };
+        tree.check();
+        tree
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&V> {
+        self.values.get(index)
+    }
+
+    fn check(&self) {
+        for (index, &parent) in self.ancestors[0].iter().enumerate() {
+            if parent == index {
+                assert_eq!(self.depths[index], 0);
+            } else {
+                assert!(self.values[parent] <= self.values[index]);
+                assert_eq!(self.depths[index], self.depths[parent] + 1);
+            }
+        }
+    }
+
+    // move up from index by the given number of levels
+    fn lift(&self, mut index: usize, levels: usize) -> usize {
+        for (j, ancestors) in self.ancestors.iter().enumerate() {
+            if levels & (1 << j) != 0 {
+                index = ancestors[index];
+            }
+        }
+        index
+    }
+
+    // index of the minimum among the positions from l to r included, the leftmost one on ties
+    // O(log n), as a lowest common ancestor query
+    pub fn range_min(&self, l: usize, r: usize) -> usize {
+        assert!(l <= r && r < self.len(), "invalid range {l}..={r}");
+        // bring both ends to the same depth
+        let (mut a, mut b) = (l, r);
+        if self.depths[a] < self.depths[b] {
+            std::mem::swap(&mut a, &mut b);
+        }
+        a = self.lift(a, self.depths[a] - self.depths[b]);
+        if a == b {
+            return a;
+        }
+        // go up as long as the ancestors differ
+        for ancestors in self.ancestors.iter().rev() {
+            if ancestors[a] != ancestors[b] {
+                a = ancestors[a];
+                b = ancestors[b];
+            }
+        }
+        self.ancestors[0][a]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test() {
+        let tree = super::CartesianTree::new(&[3, 1, 4, 1, 5, 9, 2, 6]);
+        assert_eq!(tree.range_min(0, 7), 1);
+        assert_eq!(tree.range_min(2, 7), 3);
+        assert_eq!(tree.range_min(4, 5), 4);
+        assert_eq!(tree.range_min(5, 7), 6);
+        assert_eq!(tree.range_min(5, 5), 5);
+        assert_eq!(tree.get(6), Some(&2));
+        assert!(super::CartesianTree::<i32>::new(&[]).is_empty());
+    }
+
+    #[test]
+    fn big_test() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let values: Vec<u8> = (0..1000).map(|_| rng.gen_range(0..100)).collect();
+        let tree = super::CartesianTree::new(&values);
+        for _ in 0..1000 {
+            let l = rng.gen_range(0..values.len());
+            let r = rng.gen_range(l..values.len());
+            let min = values[l..=r].iter().min().unwrap();
+            let expected = l + values[l..=r].iter().position(|x| x == min).unwrap();
+            assert_eq!(tree.range_min(l, r), expected);
+        }
+    }
+}
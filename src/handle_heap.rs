@@ -0,0 +1,201 @@
+use crate::heap::{left, parent, right};
+
+// stable identifier of a key pushed into a HandleHeap
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Handle(usize);
+
+// min-heap where keys can be reached through their handles, as needed by Dijkstra or Prim
+pub struct HandleHeap<K> {
+    nodes: Vec<(K, Handle)>,
+    // current index in nodes of each handle, None once popped
+    positions: Vec<Option<usize>>,
+}
+
+impl<K> HandleHeap<K> {
+    pub fn new() -> Self {
+        HandleHeap {
+            nodes: Vec::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // returns None if the key has already been popped
+    pub fn peek_handle(&self, handle: Handle) -> Option<&K> {
+        let index = self.positions[handle.0]?;
+        Some(&self.nodes[index].0)
+    }
+
+    // swap two nodes while keeping the positions up to date
+    fn swap(&mut self, a: usize, b: usize) {
+        self.nodes.swap(a, b);
+        self.positions[self.nodes[a].1 .0] = Some(a);
+        self.positions[self.nodes[b].1 .0] = Some(b);
+    }
+}
+
+impl<K> Default for HandleHeap<K> {
+    fn default() -> Self {
+        HandleHeap::new()
+    }
+}
+
+impl<K: Ord> HandleHeap<K> {
+    fn check(&self) {
+        for (index, (key, handle)) in self.nodes.iter().enumerate() {
+            assert_eq!(self.positions[handle.0], Some(index));
+            if index > 0 {
+                assert!(*key >= self.nodes[parent(index)].0);
+            }
+        }
+        let live = self
+            .positions
+            .iter()
+            .filter(|position| position.is_some())
+            .count();
+        assert_eq!(live, self.nodes.len());
+    }
+
+    fn bubble_up(&mut self, index: usize) {
+        if index == 0 {
+            return;
+        }
+        if self.nodes[index].0 < self.nodes[parent(index)].0 {
+            self.swap(index, parent(index));
+            self.bubble_up(parent(index));
+        }
+    }
+
+    fn bubble_down(&mut self, index: usize) {
+        let mut smallest = index;
+        for child in [left(index), right(index)] {
+            if child < self.nodes.len() && self.nodes[child].0 < self.nodes[smallest].0 {
+                smallest = child;
+            }
+        }
+        if smallest != index {
+            self.swap(index, smallest);
+            self.bubble_down(smallest);
+        }
+    }
+
+    pub fn push(&mut self, key: K) -> Handle {
+        let handle = Handle(self.positions.len());
+        self.positions.push(Some(self.nodes.len()));
+        self.nodes.push((key, handle));
+        self.bubble_up(self.nodes.len() - 1);
+        self.check();
+        handle
+    }
+
+    pub fn peek(&self) -> Option<(&K, Handle)> {
+        let (key, handle) = self.nodes.first()?;
+        Some((key, *handle))
+    }
+
+    pub fn pop(&mut self) -> Option<(K, Handle)> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let last = self.nodes.len() - 1;
+        self.swap(0, last);
+        let (key, handle) = self.nodes.pop().unwrap();
+        self.positions[handle.0] = None;
+        if !self.nodes.is_empty() {
+            self.bubble_down(0);
+        }
+        self.check();
+        Some((key, handle))
+    }
+
+    // panics if the key has already been popped or if key is greater than the current one
+    pub fn decrease_key(&mut self, handle: Handle, key: K) {
+        let index = self.positions[handle.0].expect("the key has already been popped");
+        assert!(key <= self.nodes[index].0);
+        self.nodes[index].0 = key;
+        self.bubble_up(index);
+        self.check();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn test() {
+        let mut heap = super::HandleHeap::new();
+        let handles: Vec<_> = [4, 2, 1, 3, 5, 7, 9, 6].map(|x| heap.push(x)).into();
+        assert_eq!(heap.peek_handle(handles[6]), Some(&9));
+        heap.decrease_key(handles[6], 0);
+        assert_eq!(heap.peek(), Some((&0, handles[6])));
+        assert_eq!(heap.pop(), Some((0, handles[6])));
+        assert_eq!(heap.peek_handle(handles[6]), None);
+        heap.decrease_key(handles[5], 2);
+        let mut actual = Vec::new();
+        while let Some((x, _)) = heap.pop() {
+            actual.push(x);
+        }
+        assert_eq!(actual, vec![1, 2, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn dijkstra() {
+        const N: usize = 50;
+        const INF: u64 = u64::MAX;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut weights = [[INF; N]; N];
+        for _ in 0..300 {
+            let (u, v) = (rng.gen_range(0..N), rng.gen_range(0..N));
+            weights[u][v] = weights[u][v].min(rng.gen_range(1..100));
+        }
+
+        // Floyd-Warshall as an oracle
+        let mut expected = weights;
+        for (u, row) in expected.iter_mut().enumerate() {
+            row[u] = 0;
+        }
+        for k in 0..N {
+            for u in 0..N {
+                for v in 0..N {
+                    if expected[u][k] != INF && expected[k][v] != INF {
+                        expected[u][v] = expected[u][v].min(expected[u][k] + expected[k][v]);
+                    }
+                }
+            }
+        }
+
+        for (source, expected) in expected.iter().enumerate() {
+            let mut heap = super::HandleHeap::new();
+            let handles: Vec<_> = (0..N)
+                .map(|u| heap.push((if u == source { 0 } else { INF }, u)))
+                .collect();
+            let mut actual = [INF; N];
+            while let Some(((distance, u), _)) = heap.pop() {
+                actual[u] = distance;
+                if distance == INF {
+                    continue;
+                }
+                for v in 0..N {
+                    if weights[u][v] == INF {
+                        continue;
+                    }
+                    let candidate = distance + weights[u][v];
+                    if let Some(&(current, _)) = heap.peek_handle(handles[v]) {
+                        if candidate < current {
+                            heap.decrease_key(handles[v], (candidate, v));
+                        }
+                    }
+                }
+            }
+            assert_eq!(actual, *expected);
+        }
+    }
+}
@@ -1,10 +1,56 @@
 pub struct Heap<K> {
     nodes: Vec<K>,
+    // number of swaps done by bubble_up and bubble_down
+    #[cfg(feature = "profiling")]
+    swaps: u64,
 }
 
 impl<K> Heap<K> {
     pub fn new() -> Self {
-        Heap { nodes: Vec::new() }
+        Heap::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Heap {
+            nodes: Vec::with_capacity(capacity),
+            #[cfg(feature = "profiling")]
+            swaps: 0,
+        }
+    }
+
+    #[cfg(feature = "profiling")]
+    pub fn swap_count(&self) -> u64 {
+        self.swaps
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.nodes.capacity()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // remove all the keys, keeping the allocation
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+    }
+
+    // the heap invariant might be broken by the caller, rebuild() must then be called
+    // before using the heap again
+    pub fn as_mut_slice(&mut self) -> &mut [K] {
+        &mut self.nodes
+    }
+
+    // (parent, child) pairs of indices in the backing array
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        (1..self.nodes.len())
+            .map(|child| (parent(child), child))
+            .collect()
     }
 }
 
@@ -14,16 +60,57 @@ impl<K> Default for Heap<K> {
     }
 }
 
-fn left(index: usize) -> usize {
+pub(crate) fn left(index: usize) -> usize {
     2 * index + 1
 }
-fn right(index: usize) -> usize {
+pub(crate) fn right(index: usize) -> usize {
     2 * index + 2
 }
-fn parent(index: usize) -> usize {
+pub(crate) fn parent(index: usize) -> usize {
     (index - 1) / 2
 }
 
+fn bubble_up<K: Ord>(heap: &mut Heap<K>, index: usize) {
+    if index == 0 {
+        return;
+    }
+    if heap.nodes[index] > heap.nodes[parent(index)] {
+        heap.nodes.swap(index, parent(index));
+        #[cfg(feature = "profiling")]
+        {
+            heap.swaps += 1;
+        }
+        bubble_up(heap, parent(index))
+    }
+}
+
+fn bubble_down<K: Ord>(heap: &mut Heap<K>, index: usize) {
+    let key = &heap.nodes[index];
+    let mut biggest_key = key;
+    let mut biggest_index = 0;
+    if let Some(left) = heap.nodes.get(left(index)) {
+        if *left > *biggest_key {
+            biggest_key = left;
+            biggest_index = 1;
+        }
+    }
+    if let Some(right) = heap.nodes.get(right(index)) {
+        if *right > *biggest_key {
+            // biggest_key = right;
+            biggest_index = 2;
+        }
+    }
+    if biggest_index != 0 {
+        let child = 2 * index + biggest_index;
+        heap.nodes.swap(index, child);
+        #[cfg(feature = "profiling")]
+        {
+            heap.swaps += 1;
+        }
+        bubble_down(heap, child);
+    }
+}
+
 impl<K: std::fmt::Display> Heap<K> {
     pub fn print(&self) {
         fn aux<K: std::fmt::Display>(heap: &Heap<K>, index: usize, depth: usize) {
@@ -39,6 +126,21 @@ impl<K: std::fmt::Display> Heap<K> {
         }
         aux(self, 0, 0);
     }
+
+    // Graphviz rendering of the implicit tree
+    pub fn to_dot(&self) -> String {
+        let mut ret = String::from("digraph {\n");
+        for (index, key) in self.nodes.iter().enumerate() {
+            // quotes and backslashes must be escaped in DOT strings
+            let label = key.to_string().replace('\\', "\\\\").replace('"', "\\\"");
+            ret.push_str(&format!("    {} [label=\"{}\"];\n", index, label));
+        }
+        for (parent, child) in self.edges() {
+            ret.push_str(&format!("    {} -> {};\n", parent, child));
+        }
+        ret.push_str("}\n");
+        ret
+    }
 }
 
 impl<K: Ord> Heap<K> {
@@ -48,7 +150,7 @@ impl<K: Ord> Heap<K> {
                 let key = &heap.nodes[index];
                 // ensure order is correct
                 if let Some(parent_key) = parent_key {
-                    assert!(*key < *parent_key);
+                    assert!(*key <= *parent_key);
                 }
                 aux(heap, left(index), Some(key));
                 aux(heap, right(index), Some(key));
@@ -57,48 +159,58 @@ impl<K: Ord> Heap<K> {
         aux(self, 0, None);
     }
 
-    pub fn push(&mut self, key: K) {
-        fn bubble_up<K: Ord>(heap: &mut Heap<K>, index: usize) {
-            if index == 0 {
-                return;
-            }
-            if heap.nodes[index] > heap.nodes[parent(index)] {
-                heap.nodes.swap(index, parent(index));
-                bubble_up(heap, parent(index))
-            }
+    // whether every key is at most its parent, without panicking
+    pub fn is_valid(&self) -> bool {
+        (1..self.nodes.len()).all(|index| self.nodes[index] <= self.nodes[parent(index)])
+    }
+
+    // restore the heap invariant over the whole array in O(n)
+    pub fn rebuild(&mut self) {
+        for index in (0..self.nodes.len() / 2).rev() {
+            bubble_down(self, index);
         }
+        self.check();
+    }
+
+    pub fn push(&mut self, key: K) {
         self.nodes.push(key);
         bubble_up(self, self.nodes.len() - 1);
         self.check();
     }
 
+    // push item only if pred accepts the current top, otherwise hand it back
+    pub fn push_if<F: FnOnce(Option<&K>) -> bool>(&mut self, item: K, pred: F) -> Result<(), K> {
+        if pred(self.peek()) {
+            self.push(item);
+            Ok(())
+        } else {
+            Err(item)
+        }
+    }
+
+    // push item only if this does not need to grow the allocation, otherwise hand it back
+    pub fn push_within_capacity(&mut self, item: K) -> Result<(), K> {
+        if self.nodes.len() == self.nodes.capacity() {
+            return Err(item);
+        }
+        self.push(item);
+        Ok(())
+    }
+
     pub fn peek(&self) -> Option<&K> {
         self.nodes.first()
     }
 
+    // the largest and second largest keys, the latter being a child of the root
+    pub fn peek_top2(&self) -> (Option<&K>, Option<&K>) {
+        let second = match (self.nodes.get(left(0)), self.nodes.get(right(0))) {
+            (Some(left), Some(right)) => Some(left.max(right)),
+            (left, right) => left.or(right),
+        };
+        (self.peek(), second)
+    }
+
     pub fn pop(&mut self) -> Option<K> {
-        fn bubble_down<K: Ord>(heap: &mut Heap<K>, index: usize) {
-            let key = &heap.nodes[index];
-            let mut biggest_key = key;
-            let mut biggest_index = 0;
-            if let Some(left) = heap.nodes.get(left(index)) {
-                if *left > *biggest_key {
-                    biggest_key = left;
-                    biggest_index = 1;
-                }
-            }
-            if let Some(right) = heap.nodes.get(right(index)) {
-                if *right > *biggest_key {
-                    // biggest_key = right;
-                    biggest_index = 2;
-                }
-            }
-            if biggest_index != 0 {
-                let child = 2 * index + biggest_index;
-                heap.nodes.swap(index, child);
-                bubble_down(heap, child);
-            }
-        }
         if self.nodes.is_empty() {
             None
         } else {
@@ -110,10 +222,203 @@ impl<K: Ord> Heap<K> {
             Some(ret)
         }
     }
+
+    // pop up to n keys in decreasing order into out, checking the invariant only once
+    pub fn pop_n(&mut self, n: usize, out: &mut Vec<K>) {
+        let n = n.min(self.nodes.len());
+        out.reserve(n);
+        for _ in 0..n {
+            out.push(self.nodes.swap_remove(0));
+            if !self.nodes.is_empty() {
+                bubble_down(self, 0);
+            }
+        }
+        self.check();
+    }
+
+    // pop all the keys equal to the maximum
+    pub fn pop_all_equal(&mut self) -> Vec<K> {
+        let Some(first) = self.pop() else {
+            return Vec::new();
+        };
+        let mut ret = vec![first];
+        while self.peek() == Some(&ret[0]) {
+            ret.push(self.pop().unwrap());
+        }
+        ret
+    }
+
+    // pop all the keys in decreasing order, reusing the allocation of buf
+    pub fn drain_sorted_into(&mut self, buf: &mut Vec<K>) {
+        buf.reserve(self.nodes.len());
+        while let Some(key) = self.pop() {
+            buf.push(key);
+        }
+    }
+
+    // same as pop() followed by push(key), but with a single bubble down
+    // unlike push(key) followed by pop(), this never returns key itself
+    pub fn pop_push(&mut self, key: K) -> Option<K> {
+        let Some(root) = self.nodes.first_mut() else {
+            self.push(key);
+            return None;
+        };
+        let ret = std::mem::replace(root, key);
+        bubble_down(self, 0);
+        self.check();
+        Some(ret)
+    }
+
+    // drain the keys in decreasing order, by chunks of the given size (the last might be shorter)
+    pub fn sorted_chunks(mut self, chunk: usize) -> impl Iterator<Item = Vec<K>> {
+        assert!(chunk > 0, "chunk size must be non-zero");
+        std::iter::from_fn(move || {
+            let mut ret = Vec::with_capacity(chunk.min(self.len()));
+            while ret.len() < chunk {
+                let Some(key) = self.pop() else {
+                    break;
+                };
+                ret.push(key);
+            }
+            (!ret.is_empty()).then_some(ret)
+        })
+    }
+
+    // add the keys of lower, which must all be lower than the keys of self, in O(lower.len())
+    pub fn meld_ordered(&mut self, lower: Heap<K>) {
+        let start = self.nodes.len();
+        self.nodes.extend(lower.nodes);
+        // the keys of self stay above, only the subtrees made of the keys of lower need fixing
+        for index in (start..self.nodes.len() / 2).rev() {
+            bubble_down(self, index);
+        }
+        self.check();
+    }
+
+    pub fn retain<F: FnMut(&K) -> bool>(&mut self, f: F) {
+        self.nodes.retain(f);
+        self.rebuild();
+    }
+
+    // apply f to the first key equal to target, then move it to its new place
+    // returns whether such a key was found
+    pub fn change<F: FnMut(&mut K)>(&mut self, target: &K, mut f: F) -> bool {
+        let Some(index) = self.nodes.iter().position(|key| key == target) else {
+            return false;
+        };
+        f(&mut self.nodes[index]);
+        if index > 0 && self.nodes[index] > self.nodes[parent(index)] {
+            bubble_up(self, index);
+        } else {
+            bubble_down(self, index);
+        }
+        self.check();
+        true
+    }
+}
+
+// merge iterators sorted in decreasing order into a single one
+pub fn k_way_merge<K: Ord, I: Iterator<Item = K>>(mut sources: Vec<I>) -> impl Iterator<Item = K> {
+    // the heap holds the next key of each source
+    let mut heap = Heap::new();
+    for (index, source) in sources.iter_mut().enumerate() {
+        if let Some(key) = source.next() {
+            heap.push((key, index));
+        }
+    }
+    std::iter::from_fn(move || {
+        let (key, index) = heap.pop()?;
+        if let Some(next) = sources[index].next() {
+            heap.push((next, index));
+        }
+        Some(key)
+    })
+}
+
+// the n smallest keys in increasing order, using O(n) memory
+pub fn n_smallest<K: Ord, I: IntoIterator<Item = K>>(iter: I, n: usize) -> Vec<K> {
+    // the max-heap holds the n smallest keys seen so far
+    let mut heap = Heap::new();
+    for key in iter {
+        if heap.len() < n {
+            heap.push(key);
+        } else if heap.peek().is_some_and(|top| key < *top) {
+            heap.pop_push(key);
+        }
+    }
+    let mut ret = Vec::with_capacity(heap.len());
+    heap.drain_sorted_into(&mut ret);
+    ret.reverse();
+    ret
+}
+
+// maximum of each window of consecutive keys
+pub fn sliding_max<K: Ord + Clone, I: Iterator<Item = K>>(
+    iter: I,
+    window: usize,
+) -> impl Iterator<Item = K> {
+    assert!(window > 0, "window size must be non-zero");
+    // keys are paired with their positions, those out of the window are only dropped
+    // when they reach the top
+    let mut heap = Heap::new();
+    iter.enumerate().filter_map(move |(index, key)| {
+        heap.push((key, index));
+        if index + 1 < window {
+            return None;
+        }
+        while heap
+            .peek()
+            .is_some_and(|&(_, position)| position + window <= index)
+        {
+            heap.pop();
+        }
+        heap.peek().map(|(key, _)| key.clone())
+    })
+}
+
+// sort v in increasing order, keeping equal keys in their original order
+pub fn heapsort_stable<K: Ord + Clone>(v: &mut [K]) {
+    // ties are broken by the original index, which makes all the entries distinct
+    let mut heap = Heap {
+        nodes: v.iter().cloned().zip(0..).collect(),
+        #[cfg(feature = "profiling")]
+        swaps: 0,
+    };
+    heap.rebuild();
+    // the greatest entries go to the end
+    for slot in v.iter_mut().rev() {
+        let (key, _) = heap.pop().unwrap();
+        *slot = key;
+    }
+}
+
+impl<K: Ord> From<std::collections::BinaryHeap<K>> for Heap<K> {
+    fn from(heap: std::collections::BinaryHeap<K>) -> Self {
+        let mut ret = Heap::new();
+        ret.nodes = heap.into_vec();
+        ret.rebuild();
+        ret
+    }
+}
+
+impl<K: Ord> From<Heap<K>> for std::collections::BinaryHeap<K> {
+    fn from(heap: Heap<K>) -> Self {
+        std::collections::BinaryHeap::from(heap.nodes)
+    }
+}
+
+// consuming iterator, in arbitrary order
+impl<K> IntoIterator for Heap<K> {
+    type IntoIter = std::vec::IntoIter<K>;
+    type Item = K;
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes.into_iter()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use rand::seq::SliceRandom;
     use rand::{Rng, SeedableRng};
 
     #[test]
@@ -145,4 +450,423 @@ mod tests {
         }
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn retain() {
+        let mut heap = super::Heap::new();
+        // with duplicates
+        for x in 0..100 {
+            heap.push(x / 2);
+        }
+        heap.retain(|x| x % 2 == 0);
+        heap.check();
+        let mut actual = Vec::new();
+        while let Some(x) = heap.pop() {
+            actual.push(x);
+        }
+        let expected: Vec<_> = (0..100)
+            .rev()
+            .map(|x| x / 2)
+            .filter(|x| x % 2 == 0)
+            .collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn into_iter() {
+        let mut heap = super::Heap::new();
+        for x in [4, 2, 1, 3, 5, 7, 9, 6] {
+            heap.push(x);
+        }
+        assert_eq!(heap.len(), 8);
+        let mut actual: Vec<_> = heap.into_iter().collect();
+        actual.sort();
+        assert_eq!(actual, vec![1, 2, 3, 4, 5, 6, 7, 9]);
+    }
+
+    #[test]
+    fn change() {
+        let mut heap = super::Heap::new();
+        for x in [4, 2, 1, 3, 5, 7, 9, 6, 7, 2] {
+            heap.push(x);
+        }
+        assert!(heap.change(&3, |x| *x = 10));
+        assert_eq!(heap.peek(), Some(&10));
+        assert!(heap.change(&10, |x| *x = 0));
+        assert_eq!(heap.peek(), Some(&9));
+        assert!(!heap.change(&3, |x| *x = 10));
+        assert!(heap.change(&7, |x| *x = 9));
+        let mut actual = Vec::new();
+        while let Some(x) = heap.pop() {
+            actual.push(x);
+        }
+        assert_eq!(actual, vec![9, 9, 7, 6, 5, 4, 2, 2, 1, 0]);
+    }
+
+    #[test]
+    fn rebuild() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut heap = super::Heap::new();
+        for x in 0..100 {
+            heap.push(x);
+        }
+        heap.as_mut_slice().shuffle(&mut rng);
+        for x in heap.as_mut_slice() {
+            *x *= 2;
+        }
+        heap.rebuild();
+        let mut actual = Vec::new();
+        while let Some(x) = heap.pop() {
+            actual.push(x);
+        }
+        let expected: Vec<_> = (0..100).rev().map(|x| 2 * x).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn k_way_merge() {
+        let sources = vec![
+            vec![9, 6, 3, 0].into_iter(),
+            vec![8, 4, 2].into_iter(),
+            vec![7, 5, 1].into_iter(),
+        ];
+        let actual: Vec<_> = super::k_way_merge(sources).collect();
+        assert_eq!(actual, vec![9, 8, 7, 6, 5, 4, 3, 2, 1, 0]);
+        let sources: Vec<std::vec::IntoIter<i32>> = Vec::new();
+        assert!(super::k_way_merge(sources).next().is_none());
+    }
+
+    #[test]
+    fn pop_push() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut heap = super::Heap::new();
+        let mut naive = super::Heap::new();
+        assert_eq!(heap.pop_push(0), None);
+        naive.push(0);
+        for _ in 0..100 {
+            let x: u64 = rng.gen_range(0..20);
+            heap.push(x);
+            naive.push(x);
+        }
+        for _ in 0..100 {
+            let x: u64 = rng.gen_range(0..20);
+            let expected = naive.pop();
+            naive.push(x);
+            assert_eq!(heap.pop_push(x), expected);
+        }
+        while let Some(x) = naive.pop() {
+            assert_eq!(heap.pop(), Some(x));
+        }
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn push_if() {
+        let mut heap = super::Heap::new();
+        assert_eq!(heap.push_if(5, |top| top.is_none()), Ok(()));
+        assert_eq!(heap.push_if(3, |top| top.is_none()), Err(3));
+        assert_eq!(
+            heap.push_if(7, |top| top.is_some_and(|top| 7 > *top)),
+            Ok(())
+        );
+        assert_eq!(
+            heap.push_if(6, |top| top.is_some_and(|top| 6 > *top)),
+            Err(6)
+        );
+        assert_eq!(
+            heap.push_if(7, |top| top.is_some_and(|top| 7 >= *top)),
+            Ok(())
+        );
+        assert_eq!(heap.len(), 3);
+        assert_eq!(heap.pop(), Some(7));
+        assert_eq!(heap.pop(), Some(7));
+        assert_eq!(heap.pop(), Some(5));
+    }
+
+    #[test]
+    fn drain_sorted_into() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut heap = super::Heap::new();
+        for _ in 0..100 {
+            heap.push(rng.gen_range(0u64..20));
+        }
+        let mut buf = Vec::with_capacity(100);
+        let capacity = buf.capacity();
+        heap.drain_sorted_into(&mut buf);
+        assert_eq!(buf.capacity(), capacity);
+        assert_eq!(buf.len(), 100);
+        assert!(buf.windows(2).all(|w| w[0] >= w[1]));
+        assert!(heap.is_empty());
+    }
+
+    #[test]
+    fn n_smallest() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let values: Vec<u64> = (0..1000).map(|_| rng.gen_range(0..100)).collect();
+        let mut sorted = values.clone();
+        sorted.sort();
+        for n in [0, 1, 10, 1000, 2000] {
+            let expected: Vec<_> = sorted.iter().copied().take(n).collect();
+            assert_eq!(super::n_smallest(values.iter().copied(), n), expected);
+        }
+    }
+
+    #[test]
+    fn push_within_capacity() {
+        let mut heap = super::Heap::with_capacity(4);
+        let capacity = heap.capacity();
+        for x in 0..capacity {
+            assert_eq!(heap.push_within_capacity(x), Ok(()));
+        }
+        assert_eq!(heap.push_within_capacity(capacity), Err(capacity));
+        assert_eq!(heap.capacity(), capacity);
+        assert_eq!(heap.len(), capacity);
+        for x in (0..capacity).rev() {
+            assert_eq!(heap.pop(), Some(x));
+        }
+    }
+
+    #[test]
+    fn peek_top2() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut heap = super::Heap::new();
+        assert_eq!(heap.peek_top2(), (None, None));
+        heap.push(5);
+        assert_eq!(heap.peek_top2(), (Some(&5), None));
+        heap.pop();
+        let mut expected = Vec::new();
+        for _ in 0..100 {
+            let x: u64 = rng.gen_range(0..20);
+            heap.push(x);
+            expected.push(x);
+            expected.sort();
+            let n = expected.len();
+            assert_eq!(heap.peek_top2().0, expected.last());
+            assert_eq!(heap.peek_top2().1, n.checked_sub(2).map(|i| &expected[i]));
+        }
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn swap_count() {
+        let mut heap = super::Heap::new();
+        for x in (0..100).rev() {
+            heap.push(x);
+        }
+        assert_eq!(heap.swap_count(), 0);
+        let mut heap = super::Heap::new();
+        for x in 0..100 {
+            heap.push(x);
+        }
+        assert!(heap.swap_count() > 400);
+        let before = heap.swap_count();
+        heap.pop();
+        assert!(heap.swap_count() > before);
+    }
+
+    #[test]
+    fn clear() {
+        let mut heap = super::Heap::new();
+        for x in 0..1000 {
+            heap.push(x);
+        }
+        let capacity = heap.capacity();
+        heap.clear();
+        assert!(heap.is_empty());
+        assert_eq!(heap.len(), 0);
+        assert_eq!(heap.capacity(), capacity);
+        assert_eq!(heap.pop(), None);
+    }
+
+    #[test]
+    fn sorted_chunks() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut heap = super::Heap::new();
+        for _ in 0..1000 {
+            heap.push(rng.gen_range(0u64..50));
+        }
+        let chunks: Vec<_> = heap.sorted_chunks(100).collect();
+        assert_eq!(chunks.len(), 10);
+        assert!(chunks.iter().all(|chunk| chunk.len() == 100));
+        let all: Vec<_> = chunks.concat();
+        assert!(all.windows(2).all(|w| w[0] >= w[1]));
+
+        let mut heap = super::Heap::new();
+        for x in 0..25 {
+            heap.push(x);
+        }
+        let lens: Vec<_> = heap.sorted_chunks(10).map(|chunk| chunk.len()).collect();
+        assert_eq!(lens, vec![10, 10, 5]);
+    }
+
+    #[test]
+    fn binary_heap() {
+        use std::collections::BinaryHeap;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let values: Vec<u64> = (0..1000).map(|_| rng.gen_range(0..100)).collect();
+        let std_heap: BinaryHeap<u64> = values.iter().copied().collect();
+        let mut heap = super::Heap::from(std_heap.clone());
+        let mut expected = std_heap.into_sorted_vec();
+        expected.reverse();
+        for &x in &expected {
+            assert_eq!(heap.pop(), Some(x));
+        }
+        let mut heap = super::Heap::new();
+        for &x in &values {
+            heap.push(x);
+        }
+        let mut std_heap = BinaryHeap::from(heap);
+        for &x in &expected {
+            assert_eq!(std_heap.pop(), Some(x));
+        }
+    }
+
+    #[test]
+    fn meld_ordered() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        for (upper_len, lower_len) in [(0, 10), (10, 0), (1, 100), (100, 1), (500, 700)] {
+            let mut upper = super::Heap::new();
+            let mut lower = super::Heap::new();
+            let mut expected = super::Heap::new();
+            for _ in 0..upper_len {
+                let x = rng.gen_range(100u64..200);
+                upper.push(x);
+                expected.push(x);
+            }
+            for _ in 0..lower_len {
+                let x = rng.gen_range(0u64..=100);
+                lower.push(x);
+                expected.push(x);
+            }
+            upper.meld_ordered(lower);
+            assert_eq!(upper.len(), upper_len + lower_len);
+            while let Some(x) = expected.pop() {
+                assert_eq!(upper.pop(), Some(x));
+            }
+        }
+    }
+
+    #[test]
+    fn pop_n() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut heap = super::Heap::new();
+        for _ in 0..1000 {
+            heap.push(rng.gen_range(0u64..50));
+        }
+        let mut out = Vec::new();
+        heap.pop_n(500, &mut out);
+        assert_eq!(out.len(), 500);
+        assert_eq!(heap.len(), 500);
+        assert!(out.windows(2).all(|w| w[0] >= w[1]));
+        assert!(heap.peek() <= out.last());
+        heap.pop_n(1000, &mut out);
+        assert_eq!(out.len(), 1000);
+        assert!(heap.is_empty());
+        assert!(out.windows(2).all(|w| w[0] >= w[1]));
+    }
+
+    #[test]
+    fn to_dot() {
+        let mut heap = super::Heap::new();
+        assert!(heap.edges().is_empty());
+        assert_eq!(heap.to_dot(), "digraph {\n}\n");
+        for x in [1, 2, 3, 4] {
+            heap.push(x);
+        }
+        assert_eq!(heap.edges(), vec![(0, 1), (0, 2), (1, 3)]);
+        let dot = heap.to_dot();
+        assert!(dot.starts_with("digraph {\n"));
+        assert!(dot.contains("    0 [label=\"4\"];\n"));
+        assert!(dot.contains("    1 -> 3;\n"));
+
+        let mut heap = super::Heap::new();
+        heap.push(String::from(r#"a "quoted" \ key"#));
+        assert!(heap
+            .to_dot()
+            .contains(r#"    0 [label="a \"quoted\" \\ key"];"#));
+    }
+
+    #[test]
+    fn heapsort_stable() {
+        #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+        struct Tagged {
+            key: u8,
+            // ignored by the comparisons
+            tag: Ignored,
+        }
+        #[derive(Clone, Debug)]
+        struct Ignored(usize);
+        impl PartialEq for Ignored {
+            fn eq(&self, _: &Self) -> bool {
+                true
+            }
+        }
+        impl Eq for Ignored {}
+        impl PartialOrd for Ignored {
+            fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+                Some(self.cmp(other))
+            }
+        }
+        impl Ord for Ignored {
+            fn cmp(&self, _: &Self) -> std::cmp::Ordering {
+                std::cmp::Ordering::Equal
+            }
+        }
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut v: Vec<_> = (0..1000)
+            .map(|tag| Tagged {
+                key: rng.gen_range(0..10),
+                tag: Ignored(tag),
+            })
+            .collect();
+        let mut expected = v.clone();
+        expected.sort_by_key(|x| x.key);
+        super::heapsort_stable(&mut v);
+        let actual: Vec<_> = v.iter().map(|x| (x.key, x.tag.0)).collect();
+        let expected: Vec<_> = expected.iter().map(|x| (x.key, x.tag.0)).collect();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn is_valid() {
+        let mut heap = super::Heap::new();
+        assert!(heap.is_valid());
+        for x in 0..100 {
+            heap.push(x);
+        }
+        assert!(heap.is_valid());
+        heap.as_mut_slice().swap(0, 50);
+        assert!(!heap.is_valid());
+        heap.rebuild();
+        assert!(heap.is_valid());
+    }
+
+    #[test]
+    fn sliding_max() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let values: Vec<u8> = (0..1000).map(|_| rng.gen_range(0..50)).collect();
+        for window in [1, 2, 10, 999, 1000, 1001] {
+            let expected: Vec<u8> = values
+                .windows(window)
+                .map(|w| *w.iter().max().unwrap())
+                .collect();
+            let actual: Vec<u8> = super::sliding_max(values.iter().copied(), window).collect();
+            assert_eq!(actual, expected);
+        }
+    }
+
+    #[test]
+    fn pop_all_equal() {
+        let mut heap = super::Heap::new();
+        assert!(heap.pop_all_equal().is_empty());
+        for x in [3, 7, 1, 7, 5, 7, 3, 9] {
+            heap.push(x);
+        }
+        assert_eq!(heap.pop_all_equal(), vec![9]);
+        assert_eq!(heap.pop_all_equal(), vec![7, 7, 7]);
+        assert_eq!(heap.pop_all_equal(), vec![5]);
+        assert_eq!(heap.pop_all_equal(), vec![3, 3]);
+        assert_eq!(heap.len(), 1);
+    }
 }
@@ -1,7 +1,11 @@
 use std::cmp::Ordering;
+use std::hash::{Hash, Hasher};
+use std::ops::RangeBounds;
 
 use slotmap::{new_key_type, Key, SlotMap};
 
+use crate::util::range_bounds;
+
 new_key_type! { pub struct NodeKey; }
 type Nodes<V> = SlotMap<NodeKey, Node<V>>;
 type Anchor = NodeKey;
@@ -142,6 +146,177 @@ impl<V> ImplicitTreap<V> {
         new_node_key
     }
 
+    // attach child_key as the dir-child of node_key, and update the count of node_key
+    fn set_child(&mut self, node_key: NodeKey, dir: usize, child_key: NodeKey) {
+        if let Some(child) = self.nodes.get_mut(child_key) {
+            child.parent = node_key;
+        }
+        self.nodes[node_key].children[dir] = child_key;
+        self.update_count(node_key);
+    }
+
+    fn update_count(&mut self, node_key: NodeKey) {
+        let count = self.nodes[node_key]
+            .children
+            .iter()
+            .map(|&child_key| self.nodes.get(child_key).map_or(0, |child| child.count))
+            .sum::<usize>();
+        self.nodes[node_key].count = 1 + count;
+    }
+
+    // make node_key the root of the whole tree
+    fn set_root(&mut self, node_key: NodeKey) {
+        if let Some(node) = self.nodes.get_mut(node_key) {
+            node.parent = NodeKey::null();
+        }
+        self.root = node_key;
+    }
+
+    // split the subtree of node_key into its index first nodes and the rest
+    // the parents of the two returned nodes must be set by the caller
+    fn split(&mut self, node_key: NodeKey, index: usize) -> (NodeKey, NodeKey) {
+        let Some(node) = self.nodes.get(node_key) else {
+            return (NodeKey::null(), NodeKey::null());
+        };
+        let [left_key, right_key] = node.children;
+        let current_index = self.nodes.get(left_key).map_or(0, |left| left.count);
+        if index <= current_index {
+            let (left_key, middle_key) = self.split(left_key, index);
+            self.set_child(node_key, 0, middle_key);
+            (left_key, node_key)
+        } else {
+            let (middle_key, right_key) = self.split(right_key, index - current_index - 1);
+            self.set_child(node_key, 1, middle_key);
+            (node_key, right_key)
+        }
+    }
+
+    // concatenate the subtrees of left_key and right_key
+    // the parent of the returned node must be set by the caller
+    fn merge(&mut self, left_key: NodeKey, right_key: NodeKey) -> NodeKey {
+        let (Some(left), Some(right)) = (self.nodes.get(left_key), self.nodes.get(right_key))
+        else {
+            return if self.nodes.get(left_key).is_some() {
+                left_key
+            } else {
+                right_key
+            };
+        };
+        if left.priority > right.priority {
+            let middle_key = self.merge(left.children[1], right_key);
+            self.set_child(left_key, 1, middle_key);
+            left_key
+        } else {
+            let middle_key = self.merge(left_key, right.children[0]);
+            self.set_child(right_key, 0, middle_key);
+            right_key
+        }
+    }
+
+    // move the nodes of other into self, keeping their shape
+    // returns the key of the moved root, which is not attached to anything
+    fn adopt(&mut self, mut other: ImplicitTreap<V>) -> NodeKey {
+        fn aux<V>(nodes: &mut Nodes<V>, other: &mut Nodes<V>, node_key: NodeKey) -> NodeKey {
+            let Some(node) = other.remove(node_key) else {
+                return NodeKey::null();
+            };
+            let children = node.children.map(|child_key| aux(nodes, other, child_key));
+            let new_node_key = nodes.insert(Node {
+                children,
+                parent: NodeKey::null(),
+                ..node
+            });
+            for child_key in children {
+                if let Some(child) = nodes.get_mut(child_key) {
+                    child.parent = new_node_key;
+                }
+            }
+            new_node_key
+        }
+        aux(&mut self.nodes, &mut other.nodes, other.root)
+    }
+
+    // build a subtree from a sequence in O(n) as a Cartesian tree
+    // returns the key of its root, which is not attached to anything
+    fn build<I: IntoIterator<Item = V>>(&mut self, values: I) -> NodeKey {
+        self.build_with_priorities(values.into_iter().map(|value| (value, rand::random())))
+    }
+
+    fn build_with_priorities<I: IntoIterator<Item = (V, u64)>>(&mut self, values: I) -> NodeKey {
+        // the right spine of the subtree built so far
+        let mut stack: Vec<NodeKey> = Vec::new();
+        for (value, priority) in values {
+            let node_key = self.nodes.insert(Node {
+                priority,
+                ..Node::new(value)
+            });
+            // the nodes of the spine with lower priorities go below the new node
+            let mut left_key = NodeKey::null();
+            while let Some(&top_key) = stack.last() {
+                if self.nodes[top_key].priority > self.nodes[node_key].priority {
+                    break;
+                }
+                stack.pop();
+                // the subtree of top_key will not change anymore
+                self.update_count(top_key);
+                left_key = top_key;
+            }
+            self.set_child(node_key, 0, left_key);
+            if let Some(&top_key) = stack.last() {
+                self.set_child(top_key, 1, node_key);
+            }
+            stack.push(node_key);
+        }
+        let root = stack.first().copied().unwrap_or(NodeKey::null());
+        while let Some(node_key) = stack.pop() {
+            self.update_count(node_key);
+        }
+        root
+    }
+
+    #[cfg(test)]
+    fn from_priorities(values: Vec<(V, u64)>) -> Self {
+        let mut treap = ImplicitTreap::new();
+        let root = treap.build_with_priorities(values);
+        treap.set_root(root);
+        treap
+    }
+
+    pub fn height(&self) -> usize {
+        fn aux<V>(nodes: &Nodes<V>, node_key: NodeKey) -> usize {
+            let Some(node) = nodes.get(node_key) else {
+                return 0;
+            };
+            1 + aux(nodes, node.children[0]).max(aux(nodes, node.children[1]))
+        }
+        aux(&self.nodes, self.root)
+    }
+
+    // rebuild the sequence with fresh priorities in O(n)
+    // the keys of the nodes become invalid
+    pub fn rebalance(&mut self) {
+        // collect the nodes in order
+        let mut order = Vec::with_capacity(self.len());
+        let mut stack = Vec::new();
+        let mut node_key = self.root;
+        while !stack.is_empty() || self.nodes.get(node_key).is_some() {
+            if let Some(node) = self.nodes.get(node_key) {
+                stack.push(node_key);
+                node_key = node.children[0];
+            } else {
+                let top_key = stack.pop().unwrap();
+                order.push(top_key);
+                node_key = self.nodes[top_key].children[1];
+            }
+        }
+        let values: Vec<V> = order
+            .into_iter()
+            .map(|node_key| self.nodes.remove(node_key).unwrap().value)
+            .collect();
+        let root = self.build(values);
+        self.set_root(root);
+    }
+
     pub fn insert(&mut self, index: usize, value: V) -> NodeKey {
         // returns true when rebalancing might be needed
         fn aux<V>(
@@ -231,6 +406,13 @@ impl<V> ImplicitTreap<V> {
         aux(&self.nodes, self.root, index)
     }
 
+    // put value at index and return the previous one, None when index is out of bounds
+    pub fn replace(&mut self, index: usize, value: V) -> Option<V> {
+        let node_key = self.find(index);
+        let node = self.nodes.get_mut(node_key)?;
+        Some(std::mem::replace(&mut node.value, value))
+    }
+
     pub fn node_index(&self, mut node_key: NodeKey) -> usize {
         let mut node = &self.nodes[node_key];
         let left_key = node.children[0];
@@ -362,6 +544,161 @@ impl<V> ImplicitTreap<V> {
         let node = self.nodes.get(self.root)?;
         self.remove_at(node.count - 1)
     }
+
+    // append the elements of the slice in O(s.len() + log n)
+    pub fn extend_from_slice(&mut self, s: &[V])
+    where
+        V: Clone,
+    {
+        let right_key = self.build(s.iter().cloned());
+        let root = self.merge(self.root, right_key);
+        self.set_root(root);
+    }
+
+    // insert the whole sequence of other before index
+    // the nodes of other are moved in O(other.len()), the keys they had become invalid
+    pub fn insert_seq(&mut self, index: usize, other: ImplicitTreap<V>) {
+        assert!(index <= self.len(), "index {index} is out of bounds");
+        let middle_key = self.adopt(other);
+        let (left_key, right_key) = self.split(self.root, index);
+        let left_key = self.merge(left_key, middle_key);
+        let root = self.merge(left_key, right_key);
+        self.set_root(root);
+    }
+
+    // move the first mid elements to the end, like slice::rotate_left
+    pub fn rotate_left(&mut self, mid: usize) {
+        assert!(mid <= self.len(), "mid {mid} is out of bounds");
+        let (left_key, right_key) = self.split(self.root, mid);
+        let root = self.merge(right_key, left_key);
+        self.set_root(root);
+    }
+
+    // move the last k elements to the front, like slice::rotate_right
+    pub fn rotate_right(&mut self, k: usize) {
+        assert!(k <= self.len(), "k {k} is out of bounds");
+        self.rotate_left(self.len() - k);
+    }
+
+    // replace the elements in range with the ones of replace_with, like Vec::splice
+    pub fn splice<R: RangeBounds<usize>, I: IntoIterator<Item = V>>(
+        &mut self,
+        range: R,
+        replace_with: I,
+    ) {
+        let (start, end) = range_bounds(range, self.len());
+        let (left_key, rest_key) = self.split(self.root, start);
+        let (removed_key, right_key) = self.split(rest_key, end - start);
+        // free the nodes of the removed subtree
+        let mut stack = vec![removed_key];
+        while let Some(node_key) = stack.pop() {
+            if let Some(node) = self.nodes.remove(node_key) {
+                stack.extend(node.children);
+            }
+        }
+        let middle_key = self.build(replace_with);
+        let left_key = self.merge(left_key, middle_key);
+        let root = self.merge(left_key, right_key);
+        self.set_root(root);
+    }
+
+    pub fn for_each_range<R: RangeBounds<usize>, F: FnMut(&mut V)>(&mut self, range: R, mut f: F) {
+        // offset is the index of the leftmost node of the subtree
+        fn aux<V, F: FnMut(&mut V)>(
+            nodes: &mut Nodes<V>,
+            node_key: NodeKey,
+            offset: usize,
+            start: usize,
+            end: usize,
+            f: &mut F,
+        ) {
+            let Some(node) = nodes.get(node_key) else {
+                return;
+            };
+            let (left_key, right_key) = (node.children[0], node.children[1]);
+            let index = offset + nodes.get(left_key).map_or(0, |left| left.count);
+            if start < index {
+                aux(nodes, left_key, offset, start, end, f);
+            }
+            if start <= index && index < end {
+                f(&mut nodes[node_key].value);
+            }
+            if index + 1 < end {
+                aux(nodes, right_key, index + 1, start, end, f);
+            }
+        }
+        let (start, end) = range_bounds(range, self.len());
+        aux(&mut self.nodes, self.root, 0, start, end, &mut f);
+    }
+
+    // like slice::binary_search_by, the sequence must be sorted with respect to f
+    pub fn binary_search_by<F: FnMut(&V) -> Ordering>(&self, mut f: F) -> Result<usize, usize> {
+        let mut node_key = self.root;
+        // index of the leftmost node of the current subtree
+        let mut offset = 0;
+        while let Some(node) = self.nodes.get(node_key) {
+            let index = offset
+                + self
+                    .nodes
+                    .get(node.children[0])
+                    .map_or(0, |left| left.count);
+            match f(&node.value) {
+                Ordering::Equal => return Ok(index),
+                Ordering::Less => {
+                    offset = index + 1;
+                    node_key = node.children[1];
+                }
+                Ordering::Greater => node_key = node.children[0],
+            }
+        }
+        Err(offset)
+    }
+
+    // like slice::partition_point, pred must hold for a prefix of the sequence only
+    pub fn partition_point<P: FnMut(&V) -> bool>(&self, mut pred: P) -> usize {
+        let mut node_key = self.root;
+        // index of the leftmost node of the current subtree
+        let mut offset = 0;
+        while let Some(node) = self.nodes.get(node_key) {
+            if pred(&node.value) {
+                offset += self
+                    .nodes
+                    .get(node.children[0])
+                    .map_or(0, |left| left.count)
+                    + 1;
+                node_key = node.children[1];
+            } else {
+                node_key = node.children[0];
+            }
+        }
+        offset
+    }
+}
+
+impl<V> Extend<V> for ImplicitTreap<V> {
+    fn extend<T: IntoIterator<Item = V>>(&mut self, iter: T) {
+        for value in iter {
+            self.push(value);
+        }
+    }
+}
+
+// compare the sequences, whatever the shapes of the trees
+impl<V: PartialEq> PartialEq for ImplicitTreap<V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<V: Eq> Eq for ImplicitTreap<V> {}
+
+impl<V: Hash> Hash for ImplicitTreap<V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_usize(self.len());
+        for value in self {
+            value.hash(state);
+        }
+    }
 }
 
 impl<V> Default for ImplicitTreap<V> {
@@ -405,19 +742,23 @@ impl<V: std::fmt::Display> ImplicitTreap<V> {
 pub struct IterRef<'a, V> {
     treap: &'a ImplicitTreap<V>,
     stack: Vec<(bool, NodeKey)>,
+    // side visited first, 0 for front-to-back, 1 for back-to-front
+    dir: usize,
 }
 
 impl<'a, V> IterRef<'a, V> {
-    fn new(treap: &'a ImplicitTreap<V>) -> Self {
+    fn new(treap: &'a ImplicitTreap<V>, dir: usize) -> Self {
         if treap.nodes.get(treap.root).is_some() {
             IterRef {
                 treap,
                 stack: vec![(false, treap.root)],
+                dir,
             }
         } else {
             IterRef {
                 treap,
                 stack: vec![],
+                dir,
             }
         }
     }
@@ -429,15 +770,15 @@ impl<'a, V> Iterator for IterRef<'a, V> {
         let (explored, node_key) = self.stack.pop()?;
         if explored {
             let node = &self.treap.nodes[node_key];
-            if self.treap.nodes.get(node.children[1]).is_some() {
-                self.stack.push((false, node.children[1]));
+            if self.treap.nodes.get(node.children[1 - self.dir]).is_some() {
+                self.stack.push((false, node.children[1 - self.dir]));
             }
             Some(&node.value)
         } else {
             self.stack.push((true, node_key));
             let node = &self.treap.nodes[node_key];
-            if self.treap.nodes.get(node.children[0]).is_some() {
-                self.stack.push((false, node.children[0]));
+            if self.treap.nodes.get(node.children[self.dir]).is_some() {
+                self.stack.push((false, node.children[self.dir]));
             }
             self.next()
         }
@@ -448,14 +789,29 @@ impl<'a, V> IntoIterator for &'a ImplicitTreap<V> {
     type IntoIter = IterRef<'a, V>;
     type Item = &'a V;
     fn into_iter(self) -> Self::IntoIter {
-        IterRef::new(self)
+        IterRef::new(self, 0)
     }
 }
 
 impl<V> ImplicitTreap<V> {
-    pub fn iter(&self) -> IterRef<V> {
+    pub fn iter(&self) -> IterRef<'_, V> {
         self.into_iter()
     }
+
+    // the sequence from back to front
+    pub fn iter_rev(&self) -> IterRef<'_, V> {
+        IterRef::new(self, 1)
+    }
+
+    // like slice::chunks, the last chunk might be shorter
+    pub fn chunks(&self, size: usize) -> impl Iterator<Item = Vec<&V>> {
+        assert!(size != 0, "chunk size must be non-zero");
+        let mut iter = self.iter();
+        std::iter::from_fn(move || {
+            let chunk: Vec<_> = iter.by_ref().take(size).collect();
+            (!chunk.is_empty()).then_some(chunk)
+        })
+    }
 }
 
 impl<V: std::fmt::Display> std::ops::Index<usize> for ImplicitTreap<V> {
@@ -537,4 +893,213 @@ mod tests {
         let actual: Vec<_> = treap.iter().copied().collect();
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn for_each_range() {
+        let mut treap = super::ImplicitTreap::new();
+        for i in 0..10 {
+            treap.push(i);
+        }
+        treap.for_each_range(2..5, |x| *x += 100);
+        treap.for_each_range(8.., |x| *x *= 2);
+        treap.for_each_range(..=0, |x| *x -= 1);
+        treap.for_each_range(5..5, |_| unreachable!());
+        treap.check();
+        let actual: Vec<_> = treap.iter().copied().collect();
+        assert_eq!(actual, vec![-1, 1, 102, 103, 104, 5, 6, 7, 16, 18]);
+    }
+
+    #[test]
+    fn binary_search_by() {
+        let mut treap = super::ImplicitTreap::new();
+        for i in 0..100 {
+            treap.push(2 * i + 1);
+        }
+        for i in 0..100 {
+            assert_eq!(treap.binary_search_by(|x| x.cmp(&(2 * i + 1))), Ok(i));
+            assert_eq!(treap.binary_search_by(|x| x.cmp(&(2 * i + 2))), Err(i + 1));
+        }
+        assert_eq!(treap.binary_search_by(|x| x.cmp(&0)), Err(0));
+        let empty = super::ImplicitTreap::<i32>::new();
+        assert_eq!(empty.binary_search_by(|x| x.cmp(&0)), Err(0));
+    }
+
+    #[test]
+    fn chunks() {
+        let mut treap = super::ImplicitTreap::new();
+        for i in 0..10 {
+            treap.push(i);
+        }
+        let chunks: Vec<Vec<&i32>> = treap.chunks(4).collect();
+        assert_eq!(
+            chunks,
+            vec![vec![&0, &1, &2, &3], vec![&4, &5, &6, &7], vec![&8, &9]]
+        );
+        let actual: Vec<i32> = chunks.into_iter().flatten().copied().collect();
+        assert_eq!(actual, (0..10).collect::<Vec<_>>());
+        assert_eq!(treap.chunks(10).count(), 1);
+        assert_eq!(super::ImplicitTreap::<i32>::new().chunks(3).count(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn chunks_zero() {
+        let treap = super::ImplicitTreap::<i32>::new();
+        let _ = treap.chunks(0);
+    }
+
+    #[test]
+    fn insert_seq() {
+        fn sequence(values: std::ops::Range<i32>) -> super::ImplicitTreap<i32> {
+            let mut treap = super::ImplicitTreap::new();
+            for i in values {
+                treap.push(i);
+            }
+            treap
+        }
+        let mut treap = sequence(0..10);
+        treap.insert_seq(5, sequence(100..105));
+        treap.check();
+        treap.insert_seq(0, sequence(200..202));
+        treap.check();
+        let len = treap.len();
+        treap.insert_seq(len, sequence(300..302));
+        treap.check();
+        treap.insert_seq(3, sequence(0..0));
+        treap.check();
+        let mut empty = sequence(0..0);
+        empty.insert_seq(0, sequence(0..3));
+        empty.check();
+
+        assert_eq!(treap.len(), 19);
+        let actual: Vec<_> = treap.iter().copied().collect();
+        let mut expected = vec![200, 201];
+        expected.extend(0..5);
+        expected.extend(100..105);
+        expected.extend(5..10);
+        expected.extend(300..302);
+        assert_eq!(actual, expected);
+        assert_eq!(empty.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn extend() {
+        let mut treap = super::ImplicitTreap::new();
+        treap.extend(0..10);
+        treap.check();
+        let values: Vec<i32> = (10..1000).collect();
+        treap.extend_from_slice(&values);
+        treap.check();
+        treap.extend_from_slice(&[]);
+        treap.check();
+        assert_eq!(treap.len(), 1000);
+        let actual: Vec<_> = treap.iter().copied().collect();
+        assert_eq!(actual, (0..1000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn splice() {
+        let mut treap = super::ImplicitTreap::new();
+        treap.extend(0..4);
+        treap.splice(1..3, [9, 9]);
+        treap.check();
+        assert_eq!(treap.len(), 4);
+        assert_eq!(treap.iter().copied().collect::<Vec<_>>(), vec![0, 9, 9, 3]);
+        treap.splice(..1, []);
+        treap.check();
+        treap.splice(3.., 5..8);
+        treap.check();
+        treap.splice(1..1, [4]);
+        treap.check();
+        assert_eq!(treap.len(), 7);
+        assert_eq!(treap.nodes.len(), 7);
+        assert_eq!(
+            treap.iter().copied().collect::<Vec<_>>(),
+            vec![9, 4, 9, 3, 5, 6, 7]
+        );
+    }
+
+    #[test]
+    fn eq_hash() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+        fn hash(treap: &super::ImplicitTreap<i32>) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            treap.hash(&mut hasher);
+            hasher.finish()
+        }
+        let mut a = super::ImplicitTreap::new();
+        a.extend(0..100);
+        let mut b = super::ImplicitTreap::new();
+        for x in (0..100).rev() {
+            b.insert(0, x);
+        }
+        assert!(a == b);
+        assert_eq!(hash(&a), hash(&b));
+        b.pop();
+        assert!(a != b);
+        b.push(100);
+        assert!(a != b);
+    }
+
+    #[test]
+    fn rebalance() {
+        // decreasing priorities make a right spine
+        let values: Vec<_> = (0..1000).map(|i| (i, 1000 - i as u64)).collect();
+        let mut treap = super::ImplicitTreap::from_priorities(values);
+        treap.check();
+        assert_eq!(treap.height(), 1000);
+        treap.rebalance();
+        treap.check();
+        assert!(treap.height() < 100);
+        assert_eq!(treap.len(), 1000);
+        assert_eq!(treap.nodes.len(), 1000);
+        assert!(treap.iter().copied().eq(0..1000));
+    }
+
+    #[test]
+    fn partition_point() {
+        let mut treap = super::ImplicitTreap::new();
+        treap.extend((0..100).map(|x| 2 * x));
+        let values: Vec<i32> = treap.iter().copied().collect();
+        for threshold in -1..202 {
+            let expected = values.partition_point(|&x| x < threshold);
+            assert_eq!(treap.partition_point(|&x| x < threshold), expected);
+        }
+        let empty = super::ImplicitTreap::<i32>::new();
+        assert_eq!(empty.partition_point(|_| true), 0);
+    }
+
+    #[test]
+    fn replace() {
+        let mut treap = super::ImplicitTreap::new();
+        treap.extend(0..4);
+        assert_eq!(treap.replace(2, 9), Some(2));
+        assert_eq!(treap.replace(4, 9), None);
+        assert_eq!(treap.replace(0, 7), Some(0));
+        treap.check();
+        assert_eq!(treap.iter().copied().collect::<Vec<_>>(), vec![7, 1, 9, 3]);
+    }
+
+    #[test]
+    fn iter_rev() {
+        let mut treap = super::ImplicitTreap::new();
+        treap.extend(0..10);
+        assert!(treap.iter_rev().copied().eq((0..10).rev()));
+        assert_eq!(super::ImplicitTreap::<i32>::new().iter_rev().next(), None);
+    }
+
+    #[test]
+    fn rotate() {
+        let mut treap = super::ImplicitTreap::new();
+        treap.extend(0..10);
+        treap.rotate_left(3);
+        treap.check();
+        assert!(treap.iter().copied().eq((3..10).chain(0..3)));
+        treap.rotate_right(3);
+        treap.check();
+        assert!(treap.iter().copied().eq(0..10));
+        treap.rotate_left(10);
+        assert!(treap.iter().copied().eq(0..10));
+    }
 }
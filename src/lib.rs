@@ -1,5 +1,14 @@
+#[cfg(feature = "arena")]
+pub mod arena_avl;
 pub mod avl;
+pub mod cartesian_tree;
+pub mod handle_heap;
 pub mod heap;
 pub mod implicit_treap;
+pub mod minmax;
+pub mod priority_queue;
 pub mod recursive_heap;
+pub mod ring_buffer;
 pub mod treap;
+mod util;
+pub mod wb_avl;
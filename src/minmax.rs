@@ -0,0 +1,217 @@
+use std::cmp::Ordering;
+
+use crate::heap::{left, parent, right};
+
+// the keys on even levels are smaller than their descendants,
+// the keys on odd levels are greater than their descendants
+pub struct MinMaxHeap<K> {
+    nodes: Vec<K>,
+}
+
+impl<K> MinMaxHeap<K> {
+    pub fn new() -> Self {
+        MinMaxHeap { nodes: Vec::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+impl<K> Default for MinMaxHeap<K> {
+    fn default() -> Self {
+        MinMaxHeap::new()
+    }
+}
+
+// Less on min levels, Greater on max levels
+#[allow(clippy::manual_is_multiple_of)] // is_multiple_of needs a recent toolchain
+fn level_order(index: usize) -> Ordering {
+    if (index + 1).ilog2() % 2 == 0 {
+        Ordering::Less
+    } else {
+        Ordering::Greater
+    }
+}
+
+impl<K: Ord> MinMaxHeap<K> {
+    fn check(&self) {
+        // min and max are the bounds set by the closest ancestors on min and max levels
+        fn aux<K: Ord>(heap: &MinMaxHeap<K>, index: usize, min: Option<&K>, max: Option<&K>) {
+            let Some(key) = heap.nodes.get(index) else {
+                return;
+            };
+            if let Some(min) = min {
+                assert!(*key >= *min);
+            }
+            if let Some(max) = max {
+                assert!(*key <= *max);
+            }
+            let (min, max) = match level_order(index) {
+                Ordering::Less => (Some(key), max),
+                _ => (min, Some(key)),
+            };
+            aux(heap, left(index), min, max);
+            aux(heap, right(index), min, max);
+        }
+        aux(self, 0, None, None);
+    }
+
+    // move the key at index up among the nodes of the same kind of level
+    fn bubble_up_level(&mut self, index: usize, order: Ordering) {
+        if index < 3 {
+            return;
+        }
+        let grandparent = parent(parent(index));
+        if self.nodes[index].cmp(&self.nodes[grandparent]) == order {
+            self.nodes.swap(index, grandparent);
+            self.bubble_up_level(grandparent, order);
+        }
+    }
+
+    fn bubble_up(&mut self, index: usize) {
+        if index == 0 {
+            return;
+        }
+        let order = level_order(index);
+        let parent = parent(index);
+        if self.nodes[index].cmp(&self.nodes[parent]) == order.reverse() {
+            // the key belongs to the levels of the parent
+            self.nodes.swap(index, parent);
+            self.bubble_up_level(parent, order.reverse());
+        } else {
+            self.bubble_up_level(index, order);
+        }
+    }
+
+    fn bubble_down(&mut self, index: usize) {
+        let order = level_order(index);
+        // find the most extreme among children and grandchildren
+        let children = [left(index), right(index)];
+        let grandchildren = children.map(|child| [left(child), right(child)]);
+        let Some(best) = children
+            .into_iter()
+            .chain(grandchildren.into_iter().flatten())
+            .filter(|&candidate| candidate < self.nodes.len())
+            .reduce(|best, candidate| {
+                if self.nodes[candidate].cmp(&self.nodes[best]) == order {
+                    candidate
+                } else {
+                    best
+                }
+            })
+        else {
+            return;
+        };
+        if self.nodes[best].cmp(&self.nodes[index]) != order {
+            return;
+        }
+        self.nodes.swap(index, best);
+        if parent(best) != index {
+            // best was a grandchild, the key from index might not fit between the levels
+            if self.nodes[best].cmp(&self.nodes[parent(best)]) == order.reverse() {
+                self.nodes.swap(best, parent(best));
+            }
+            self.bubble_down(best);
+        }
+    }
+
+    pub fn push(&mut self, key: K) {
+        self.nodes.push(key);
+        self.bubble_up(self.nodes.len() - 1);
+        self.check();
+    }
+
+    pub fn peek_min(&self) -> Option<&K> {
+        self.nodes.first()
+    }
+
+    fn max_index(&self) -> Option<usize> {
+        match self.nodes.len() {
+            0 => None,
+            1 => Some(0),
+            2 => Some(1),
+            _ => Some(if self.nodes[1] >= self.nodes[2] { 1 } else { 2 }),
+        }
+    }
+
+    pub fn peek_max(&self) -> Option<&K> {
+        Some(&self.nodes[self.max_index()?])
+    }
+
+    fn remove_at(&mut self, index: usize) -> K {
+        let ret = self.nodes.swap_remove(index);
+        if index < self.nodes.len() {
+            self.bubble_down(index);
+        }
+        self.check();
+        ret
+    }
+
+    pub fn pop_min(&mut self) -> Option<K> {
+        if self.nodes.is_empty() {
+            None
+        } else {
+            Some(self.remove_at(0))
+        }
+    }
+
+    pub fn pop_max(&mut self) -> Option<K> {
+        let index = self.max_index()?;
+        Some(self.remove_at(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test() {
+        let mut heap = super::MinMaxHeap::new();
+        for v in [4, 2, 1, 3, 5, 7, 9, 6] {
+            heap.push(v);
+        }
+        assert_eq!(heap.peek_min(), Some(&1));
+        assert_eq!(heap.peek_max(), Some(&9));
+        assert_eq!(heap.pop_max(), Some(9));
+        assert_eq!(heap.pop_min(), Some(1));
+        assert_eq!(heap.pop_max(), Some(7));
+        assert_eq!(heap.pop_min(), Some(2));
+        assert_eq!(heap.len(), 4);
+        while heap.pop_min().is_some() {}
+        assert_eq!(heap.peek_max(), None);
+        assert_eq!(heap.pop_max(), None);
+    }
+
+    #[test]
+    fn big_test() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut heap = super::MinMaxHeap::new();
+        let mut expected = Vec::new();
+
+        for _ in 0..10000 {
+            let x: u8 = rng.gen();
+            heap.push(x);
+            expected.push(x);
+        }
+        expected.sort();
+        let mut expected = VecDeque::from(expected);
+
+        while !expected.is_empty() {
+            assert_eq!(heap.peek_min(), expected.front());
+            assert_eq!(heap.peek_max(), expected.back());
+            if rng.gen() {
+                assert_eq!(heap.pop_min(), expected.pop_front());
+            } else {
+                assert_eq!(heap.pop_max(), expected.pop_back());
+            }
+        }
+        assert!(heap.is_empty());
+    }
+}
@@ -0,0 +1,123 @@
+use std::cmp::Ordering;
+
+use crate::heap::Heap;
+
+// orders by priority only, the earliest pushed coming first among equal priorities
+struct Entry<P, T> {
+    priority: P,
+    sequence: u64,
+    item: T,
+}
+
+impl<P: Ord, T> Ord for Entry<P, T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl<P: Ord, T> PartialOrd for Entry<P, T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<P: Ord, T> PartialEq for Entry<P, T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<P: Ord, T> Eq for Entry<P, T> {}
+
+// max-heap of items ordered by separate priorities
+pub struct PriorityQueue<P, T> {
+    heap: Heap<Entry<P, T>>,
+    // number of items pushed so far
+    sequence: u64,
+}
+
+impl<P, T> PriorityQueue<P, T> {
+    pub fn new() -> Self {
+        PriorityQueue {
+            heap: Heap::new(),
+            sequence: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+impl<P, T> Default for PriorityQueue<P, T> {
+    fn default() -> Self {
+        PriorityQueue::new()
+    }
+}
+
+impl<P: Ord, T> PriorityQueue<P, T> {
+    pub fn push(&mut self, priority: P, item: T) {
+        self.heap.push(Entry {
+            priority,
+            sequence: self.sequence,
+            item,
+        });
+        self.sequence += 1;
+    }
+
+    pub fn peek(&self) -> Option<(&P, &T)> {
+        let entry = self.heap.peek()?;
+        Some((&entry.priority, &entry.item))
+    }
+
+    pub fn pop(&mut self) -> Option<(P, T)> {
+        let entry = self.heap.pop()?;
+        Some((entry.priority, entry.item))
+    }
+}
+
+impl<P: Ord, T> FromIterator<(P, T)> for PriorityQueue<P, T> {
+    fn from_iter<I: IntoIterator<Item = (P, T)>>(iter: I) -> Self {
+        let mut queue = PriorityQueue::new();
+        for (priority, item) in iter {
+            queue.push(priority, item);
+        }
+        queue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test() {
+        let mut queue = super::PriorityQueue::new();
+        queue.push(2, "b");
+        queue.push(5, "e");
+        queue.push(1, "a");
+        queue.push(5, "f");
+        queue.push(3, "c");
+        assert_eq!(queue.len(), 5);
+        assert_eq!(queue.peek(), Some((&5, &"e")));
+        let actual: Vec<_> = std::iter::from_fn(|| queue.pop()).collect();
+        assert_eq!(
+            actual,
+            vec![(5, "e"), (5, "f"), (3, "c"), (2, "b"), (1, "a")]
+        );
+        assert!(queue.is_empty());
+
+        // the payloads do not need to be comparable
+        struct Payload(u32);
+        let mut queue: super::PriorityQueue<_, _> =
+            [(1, Payload(10)), (3, Payload(30)), (2, Payload(20))]
+                .into_iter()
+                .collect();
+        assert_eq!(queue.pop().map(|(p, Payload(x))| (p, x)), Some((3, 30)));
+        assert_eq!(queue.pop().map(|(p, Payload(x))| (p, x)), Some((2, 20)));
+    }
+}
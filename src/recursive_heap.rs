@@ -66,6 +66,24 @@ fn binary_path_to(mut index: usize) -> usize {
     (index.reverse_bits() >> index.leading_zeros()) / 2
 }
 
+fn bubble_down<K: Ord>(node: &mut Node<K>) {
+    let mut biggest_dir = 2;
+    let mut biggest = &node.key;
+    for dir in [0, 1] {
+        if let Some(child) = &node.children[dir] {
+            if child.key > *biggest {
+                biggest_dir = dir;
+                biggest = &child.key;
+            }
+        }
+    }
+    if biggest_dir != 2 {
+        let child = node.children[biggest_dir].as_mut().unwrap();
+        std::mem::swap(&mut node.key, &mut child.key);
+        bubble_down(child);
+    }
+}
+
 impl<K: Ord> RecursiveHeap<K> {
     fn check(&self) {
         // returns the number of nodes
@@ -75,7 +93,7 @@ impl<K: Ord> RecursiveHeap<K> {
             };
             // ensure order is correct
             if let Some(parent_key) = parent_key {
-                assert!(node.key < *parent_key);
+                assert!(node.key <= *parent_key);
             }
             // ensure the tree is complete
             if node.children[0].is_none() {
@@ -121,23 +139,6 @@ impl<K: Ord> RecursiveHeap<K> {
                 last_key(node.children[dir].as_mut().unwrap(), path / 2)
             }
         }
-        fn bubble_down<K: Ord>(node: &mut Node<K>) {
-            let mut biggest_dir = 2;
-            let mut biggest = &node.key;
-            for dir in [0, 1] {
-                if let Some(child) = &node.children[dir] {
-                    if child.key > *biggest {
-                        biggest_dir = dir;
-                        biggest = &child.key;
-                    }
-                }
-            }
-            if biggest_dir != 2 {
-                let child = node.children[biggest_dir].as_mut().unwrap();
-                std::mem::swap(&mut node.key, &mut child.key);
-                bubble_down(child);
-            }
-        }
         let node = self.root.as_mut()?;
         let ret = if self.size == 1 {
             self.size -= 1;
@@ -153,11 +154,84 @@ impl<K: Ord> RecursiveHeap<K> {
         self.check();
         ret
     }
+
+    // keep at most k keys, the smallest ones (the largest ones when using Reverse)
+    // returns the key which is evicted, if any
+    pub fn offer(&mut self, key: K, k: usize) -> Option<K> {
+        if self.size < k {
+            self.push(key);
+            return None;
+        }
+        let Some(root) = self.root.as_mut() else {
+            return Some(key);
+        };
+        if key >= root.key {
+            return Some(key);
+        }
+        let ret = std::mem::replace(&mut root.key, key);
+        bubble_down(root);
+        self.check();
+        Some(ret)
+    }
+
+    // apply f to the root key, meant to lower it, then move it to its new place
+    // returns whether there was a root
+    pub fn decrease_root<F: FnOnce(&mut K)>(&mut self, f: F) -> bool {
+        let Some(root) = self.root.as_mut() else {
+            return false;
+        };
+        f(&mut root.key);
+        bubble_down(root);
+        self.check();
+        true
+    }
+
+    // the keys in breadth-first order, which is enough to rebuild the heap
+    pub fn level_order(&self) -> Vec<&K> {
+        let mut ret = Vec::new();
+        let mut queue = std::collections::VecDeque::new();
+        queue.extend(&self.root);
+        while let Some(node) = queue.pop_front() {
+            ret.push(&node.key);
+            queue.extend(node.children.iter().flatten());
+        }
+        ret
+    }
+
+    // the keys in decreasing order, leaving the heap untouched
+    pub fn to_sorted_vec(&self) -> Vec<K>
+    where
+        K: Clone,
+    {
+        let mut ret: Vec<K> = self.level_order().into_iter().cloned().collect();
+        ret.sort_by(|a, b| b.cmp(a));
+        ret
+    }
+
+    pub fn from_level_order(keys: Vec<K>) -> Self {
+        // build the complete binary tree, then restore the heap order from the bottom
+        fn aux<K: Ord>(keys: &mut [Option<K>], index: usize) -> Anchor<K> {
+            let key = keys.get_mut(index)?.take().unwrap();
+            let mut node = Box::new(Node::new(key));
+            node.children = [aux(keys, 2 * index + 1), aux(keys, 2 * index + 2)];
+            bubble_down(&mut node);
+            Some(node)
+        }
+        let size = keys.len();
+        let mut keys: Vec<_> = keys.into_iter().map(Some).collect();
+        let heap = RecursiveHeap {
+            root: aux(&mut keys, 0),
+            size,
+        };
+        heap.check();
+        heap
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use rand::{Rng, SeedableRng};
+    use std::cmp::Reverse;
 
     #[test]
     fn test() {
@@ -188,4 +262,99 @@ mod tests {
         }
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn level_order() {
+        let mut heap = super::RecursiveHeap::new();
+        for v in [4, 2, 1, 3, 5, 7, 9, 6] {
+            heap.push(v);
+        }
+        let keys: Vec<i32> = heap.level_order().into_iter().copied().collect();
+        assert_eq!(keys[0], 9);
+        let copy = super::RecursiveHeap::from_level_order(keys.clone());
+        let copied_keys: Vec<i32> = copy.level_order().into_iter().copied().collect();
+        assert_eq!(copied_keys, keys);
+
+        // arbitrary order gets heapified
+        let mut heap = super::RecursiveHeap::from_level_order(vec![1, 2, 3, 4, 5, 6, 7, 8]);
+        let mut actual = Vec::new();
+        while let Some(x) = heap.pop() {
+            actual.push(x);
+        }
+        assert_eq!(actual, vec![8, 7, 6, 5, 4, 3, 2, 1]);
+
+        // duplicate keys
+        let mut heap = super::RecursiveHeap::from_level_order(vec![2, 2, 3, 1, 3, 2, 1]);
+        let mut actual = Vec::new();
+        while let Some(x) = heap.pop() {
+            actual.push(x);
+        }
+        assert_eq!(actual, vec![3, 3, 2, 2, 2, 1, 1]);
+    }
+
+    #[test]
+    fn offer() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut heap = super::RecursiveHeap::new();
+        let mut all = Vec::new();
+        for _ in 0..1000 {
+            let x: u64 = rng.gen_range(0..100);
+            all.push(x);
+            heap.offer(Reverse(x), 10);
+        }
+        let mut actual = Vec::new();
+        while let Some(Reverse(x)) = heap.pop() {
+            actual.push(x);
+        }
+        all.sort();
+        all.reverse();
+        all.truncate(10);
+        all.reverse();
+        assert_eq!(actual, all);
+    }
+
+    #[test]
+    fn to_sorted_vec() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut heap = super::RecursiveHeap::new();
+        for _ in 0..100 {
+            heap.push(rng.gen::<u64>());
+        }
+        let sorted = heap.to_sorted_vec();
+        assert_eq!(sorted.len(), 100);
+        assert!(sorted.windows(2).all(|w| w[0] >= w[1]));
+        for x in sorted {
+            assert_eq!(heap.pop(), Some(x));
+        }
+        assert_eq!(heap.pop(), None);
+    }
+
+    #[test]
+    fn decrease_root() {
+        let mut heap = super::RecursiveHeap::new();
+        assert!(!heap.decrease_root(|x| *x = 0));
+        for x in [4, 2, 1, 3, 5, 7, 9, 6] {
+            heap.push(x * 10);
+        }
+        assert!(heap.decrease_root(|x| *x = 15));
+        assert_eq!(heap.peek(), Some(&70));
+        assert!(heap.decrease_root(|x| *x -= 5));
+        let mut actual = Vec::new();
+        while let Some(x) = heap.pop() {
+            actual.push(x);
+        }
+        assert_eq!(actual, vec![65, 60, 50, 40, 30, 20, 15, 10]);
+
+        // the lowered root ties with one of its children
+        let mut heap = super::RecursiveHeap::new();
+        for x in [5, 3, 2] {
+            heap.push(x);
+        }
+        assert!(heap.decrease_root(|x| *x = 3));
+        let mut actual = Vec::new();
+        while let Some(x) = heap.pop() {
+            actual.push(x);
+        }
+        assert_eq!(actual, vec![3, 3, 2]);
+    }
 }
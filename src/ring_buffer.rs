@@ -0,0 +1,115 @@
+use crate::implicit_treap::{ImplicitTreap, IterRef};
+
+// deque that can be rotated in O(log n)
+pub struct RingBuffer<V> {
+    treap: ImplicitTreap<V>,
+}
+
+impl<V> RingBuffer<V> {
+    pub fn new() -> Self {
+        RingBuffer {
+            treap: ImplicitTreap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.treap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.treap.is_empty()
+    }
+
+    pub fn push_back(&mut self, value: V) {
+        self.treap.push(value);
+    }
+
+    pub fn push_front(&mut self, value: V) {
+        self.treap.insert(0, value);
+    }
+
+    pub fn pop_back(&mut self) -> Option<V> {
+        self.treap.pop()
+    }
+
+    pub fn pop_front(&mut self) -> Option<V> {
+        if self.treap.is_empty() {
+            return None;
+        }
+        self.treap.remove_at(0)
+    }
+
+    // move the front element to the back n times, or the back element to the front -n times
+    pub fn rotate(&mut self, n: isize) {
+        let len = self.len();
+        if len == 0 {
+            return;
+        }
+        let mid = n.rem_euclid(len as isize) as usize;
+        self.treap.rotate_left(mid);
+    }
+
+    pub fn iter(&self) -> IterRef<'_, V> {
+        self.treap.iter()
+    }
+}
+
+impl<V> Default for RingBuffer<V> {
+    fn default() -> Self {
+        RingBuffer::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use std::collections::VecDeque;
+
+    #[test]
+    fn test() {
+        let mut ring = super::RingBuffer::new();
+        for x in 0..5 {
+            ring.push_back(x);
+        }
+        ring.rotate(2);
+        assert!(ring.iter().eq([&2, &3, &4, &0, &1]));
+        ring.rotate(-3);
+        assert!(ring.iter().eq([&4, &0, &1, &2, &3]));
+        ring.rotate(10);
+        assert!(ring.iter().eq([&4, &0, &1, &2, &3]));
+        assert_eq!(ring.pop_front(), Some(4));
+        assert_eq!(ring.pop_back(), Some(3));
+        assert_eq!(ring.len(), 3);
+    }
+
+    #[test]
+    fn big_test() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut ring = super::RingBuffer::new();
+        let mut expected = VecDeque::new();
+        for i in 0..10000 {
+            match rng.gen_range(0..5) {
+                0 => {
+                    ring.push_back(i);
+                    expected.push_back(i);
+                }
+                1 => {
+                    ring.push_front(i);
+                    expected.push_front(i);
+                }
+                2 => assert_eq!(ring.pop_back(), expected.pop_back()),
+                3 => assert_eq!(ring.pop_front(), expected.pop_front()),
+                _ => {
+                    let n = rng.gen_range(-100..100);
+                    ring.rotate(n);
+                    if !expected.is_empty() {
+                        let len = expected.len() as isize;
+                        expected.rotate_left(n.rem_euclid(len) as usize);
+                    }
+                }
+            }
+            assert_eq!(ring.len(), expected.len());
+        }
+        assert!(ring.iter().eq(expected.iter()));
+    }
+}
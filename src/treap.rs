@@ -1,30 +1,171 @@
 use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::util::merge_join;
 
 type Anchor<K> = Option<Box<Node<K>>>;
 
 struct Node<K> {
     key: K,
     priority: u64,
+    count: usize,
     children: [Anchor<K>; 2],
 }
 
 impl<K> Node<K> {
-    fn new(key: K) -> Self {
+    fn new(key: K, priority: u64) -> Self {
         Node {
             key,
-            priority: rand::random(),
+            priority,
+            count: 1,
             children: [None, None],
         }
     }
+
+    // must be called whenever the children change
+    fn update_count(&mut self) {
+        self.count = 1 + count(&self.children[0]) + count(&self.children[1]);
+    }
+}
+
+fn count<K>(anchor: &Anchor<K>) -> usize {
+    anchor.as_ref().map_or(0, |node| node.count)
+}
+
+// invariant violated by a treap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TreapError {
+    // the keys are not in binary search tree order
+    KeyOrder,
+    // a node has a higher priority than its parent
+    PriorityOrder,
+    // a node count does not match the size of its subtree
+    Count,
 }
 
+impl std::fmt::Display for TreapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreapError::KeyOrder => write!(f, "keys are out of order"),
+            TreapError::PriorityOrder => write!(f, "priorities are out of order"),
+            TreapError::Count => write!(f, "node count is wrong"),
+        }
+    }
+}
+
+impl std::error::Error for TreapError {}
+
 pub struct Treap<K> {
     root: Anchor<K>,
+    // source of the priorities, the thread-local generator when None
+    rng: Option<StdRng>,
+    // deepest position at which a key has been inserted, the root being at depth 0
+    #[cfg(feature = "profiling")]
+    worst_insert_depth: usize,
+    // number of rotations done by insertions and removals
+    #[cfg(feature = "profiling")]
+    rotations: u64,
 }
 
 impl<K> Treap<K> {
     pub fn new() -> Self {
-        Treap { root: None }
+        Treap::randomized()
+    }
+
+    pub fn randomized() -> Self {
+        Treap::with_rng(None)
+    }
+
+    // the priorities are reproducible, and so is the shape of the treap
+    pub fn seeded(seed: u64) -> Self {
+        Treap::with_rng(Some(StdRng::seed_from_u64(seed)))
+    }
+
+    fn with_rng(rng: Option<StdRng>) -> Self {
+        Treap {
+            root: None,
+            rng,
+            #[cfg(feature = "profiling")]
+            worst_insert_depth: 0,
+            #[cfg(feature = "profiling")]
+            rotations: 0,
+        }
+    }
+
+    fn gen_priority(&mut self) -> u64 {
+        match &mut self.rng {
+            Some(rng) => rng.gen(),
+            None => rand::random(),
+        }
+    }
+
+    // a generator for another treap, deterministic when self is seeded
+    fn fork_rng(&mut self) -> Option<StdRng> {
+        let rng = self.rng.as_mut()?;
+        Some(StdRng::seed_from_u64(rng.gen()))
+    }
+
+    pub fn len(&self) -> usize {
+        count(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    // the key with the highest priority, a uniformly random one with random priorities
+    pub fn peek_root(&self) -> Option<&K> {
+        self.root.as_ref().map(|node| &node.key)
+    }
+
+    // depth of the deepest node, the root being at depth 0
+    pub fn max_depth(&self) -> usize {
+        self.height().saturating_sub(1)
+    }
+
+    #[cfg(feature = "profiling")]
+    pub fn worst_insert_depth(&self) -> usize {
+        self.worst_insert_depth
+    }
+
+    #[cfg(feature = "profiling")]
+    pub fn rotation_count(&self) -> u64 {
+        self.rotations
+    }
+
+    pub fn height(&self) -> usize {
+        fn aux<K>(anchor: &Anchor<K>) -> usize {
+            let Some(node) = anchor else {
+                return 0;
+            };
+            1 + aux(&node.children[0]).max(aux(&node.children[1]))
+        }
+        aux(&self.root)
+    }
+
+    // average depth of the nodes, the root being at depth 0
+    pub fn avg_depth(&self) -> f64 {
+        // returns the number of nodes and the sum of their depths
+        fn aux<K>(anchor: &Anchor<K>, depth: usize) -> (usize, usize) {
+            let Some(node) = anchor else {
+                return (0, 0);
+            };
+            let (left_count, left_depths) = aux(&node.children[0], depth + 1);
+            let (right_count, right_depths) = aux(&node.children[1], depth + 1);
+            (
+                1 + left_count + right_count,
+                depth + left_depths + right_depths,
+            )
+        }
+        let (count, depths) = aux(&self.root, 0);
+        if count == 0 {
+            0.0
+        } else {
+            depths as f64 / count as f64
+        }
     }
 }
 
@@ -34,6 +175,24 @@ impl<K> Default for Treap<K> {
     }
 }
 
+impl<K: PartialEq> Treap<K> {
+    // compare the keys and the structure, but not the priorities
+    pub fn same_shape(&self, other: &Treap<K>) -> bool {
+        fn aux<K: PartialEq>(a: &Anchor<K>, b: &Anchor<K>) -> bool {
+            match (a, b) {
+                (None, None) => true,
+                (Some(a), Some(b)) => {
+                    a.key == b.key
+                        && aux(&a.children[0], &b.children[0])
+                        && aux(&a.children[1], &b.children[1])
+                }
+                _ => false,
+            }
+        }
+        aux(&self.root, &other.root)
+    }
+}
+
 impl<K: std::fmt::Display> Treap<K> {
     pub fn print(&self) {
         fn aux<K: std::fmt::Display>(anchor: &Anchor<K>, depth: usize) {
@@ -52,48 +211,70 @@ impl<K: std::fmt::Display> Treap<K> {
 
 impl<K: Ord> Treap<K> {
     fn check(&self) {
+        if let Err(error) = self.validate() {
+            panic!("invalid treap: {error}");
+        }
+    }
+
+    // same as check(), but reports the first violation instead of panicking
+    pub fn validate(&self) -> Result<(), TreapError> {
+        // returns the number of nodes in the subtree
         fn aux<K: Ord>(
             anchor: &Anchor<K>,
             min_key: Option<&K>,
             max_key: Option<&K>,
             parent_priority: Option<u64>,
-        ) {
+        ) -> Result<usize, TreapError> {
             let Some(node) = anchor else {
-                return;
+                return Ok(0);
             };
             // check this is a binary search tree
-            if let Some(min_key) = min_key {
-                assert!(node.key > *min_key);
-            }
-            if let Some(max_key) = max_key {
-                assert!(node.key < *max_key);
+            if min_key.is_some_and(|min_key| node.key <= *min_key)
+                || max_key.is_some_and(|max_key| node.key >= *max_key)
+            {
+                return Err(TreapError::KeyOrder);
             }
             // check this is a heap
-            if let Some(parent_priority) = parent_priority {
-                assert!(node.priority <= parent_priority);
+            if parent_priority.is_some_and(|parent_priority| node.priority > parent_priority) {
+                return Err(TreapError::PriorityOrder);
             }
             // recurse
             let prio = Some(node.priority);
-            aux(&node.children[0], min_key, Some(&node.key), prio);
-            aux(&node.children[1], Some(&node.key), max_key, prio);
+            let mut count = 1;
+            count += aux(&node.children[0], min_key, Some(&node.key), prio)?;
+            count += aux(&node.children[1], Some(&node.key), max_key, prio)?;
+            // check the node count
+            if node.count != count {
+                return Err(TreapError::Count);
+            }
+            Ok(count)
         }
-        aux(&self.root, None, None, None);
+        aux(&self.root, None, None, None)?;
+        Ok(())
     }
 
-    fn rotate(anchor: &mut Anchor<K>, dir: usize) {
+    fn rotate(anchor: &mut Anchor<K>, dir: usize, rotations: &mut u64) {
         let mut parent = anchor.take().unwrap();
         let mut new_parent = parent.children[dir].take().unwrap();
         assert!(new_parent.priority > parent.priority);
         parent.children[dir] = new_parent.children[1 - dir].take();
+        parent.update_count();
         new_parent.children[1 - dir] = Some(parent);
+        new_parent.update_count();
         *anchor = Some(new_parent);
+        *rotations += 1;
     }
 
     pub fn insert(&mut self, key: K) {
+        let priority = self.gen_priority();
+        self.insert_with_priority(key, priority);
+    }
+
+    fn insert_with_priority(&mut self, key: K, priority: u64) {
         // returns true when we should check the heap invariant
-        fn aux<K: Ord>(anchor: &mut Anchor<K>, key: K) -> bool {
+        fn aux<K: Ord>(anchor: &mut Anchor<K>, key: K, priority: u64, rotations: &mut u64) -> bool {
             let Some(node) = anchor else {
-                *anchor = Some(Box::new(Node::new(key)));
+                *anchor = Some(Box::new(Node::new(key, priority)));
                 return true;
             };
             let dir = match key.cmp(&node.key) {
@@ -101,21 +282,55 @@ impl<K: Ord> Treap<K> {
                 Ordering::Greater => 1,
                 Ordering::Equal => return false,
             };
-            if !aux(&mut node.children[dir], key) {
+            let should_check = aux(&mut node.children[dir], key, priority, rotations);
+            node.update_count();
+            if !should_check {
                 return false;
             }
             if node.children[dir].as_ref().unwrap().priority > node.priority {
                 // bubble up
-                Treap::rotate(anchor, dir);
+                Treap::rotate(anchor, dir, rotations);
                 true
             } else {
                 false
             }
         }
-        aux(&mut self.root, key);
+        #[cfg(feature = "profiling")]
+        {
+            // the new node is first attached where the search for key ends
+            let mut depth = 0;
+            let mut anchor = &self.root;
+            while let Some(node) = anchor {
+                match key.cmp(&node.key) {
+                    Ordering::Less => anchor = &node.children[0],
+                    Ordering::Greater => anchor = &node.children[1],
+                    Ordering::Equal => break,
+                }
+                depth += 1;
+            }
+            if anchor.is_none() {
+                self.worst_insert_depth = self.worst_insert_depth.max(depth);
+            }
+        }
+        let mut rotations = 0;
+        aux(&mut self.root, key, priority, &mut rotations);
+        #[cfg(feature = "profiling")]
+        {
+            self.rotations += rotations;
+        }
         self.check();
     }
 
+    // build a treap with a given shape
+    #[cfg(test)]
+    fn from_key_priority(pairs: Vec<(K, u64)>) -> Self {
+        let mut treap = Treap::new();
+        for (key, priority) in pairs {
+            treap.insert_with_priority(key, priority);
+        }
+        treap
+    }
+
     pub fn contains(&self, key: K) -> bool {
         fn aux<K: Ord>(anchor: &Anchor<K>, key: K) -> bool {
             if let Some(node) = anchor {
@@ -131,71 +346,323 @@ impl<K: Ord> Treap<K> {
         aux(&self.root, key)
     }
 
-    pub fn remove(&mut self, key: K) {
-        fn leftmost<K>(mut node: &mut Node<K>) -> Box<Node<K>> {
-            while node.children[0].as_ref().unwrap().children[0].is_some() {
-                node = node.children[0].as_mut().unwrap();
+    // number of occurrences of key, at most 1 since keys are unique
+    pub fn count(&self, key: &K) -> usize {
+        let mut anchor = &self.root;
+        while let Some(node) = anchor {
+            match key.cmp(&node.key) {
+                Ordering::Less => anchor = &node.children[0],
+                Ordering::Greater => anchor = &node.children[1],
+                Ordering::Equal => return 1,
             }
-            let mut ret = node.children[0].take().unwrap();
-            node.children[0] = ret.children[1].take();
-            assert!(ret.children[0].is_none());
-            assert!(ret.children[1].is_none());
-            ret
-        }
-        fn bubble_down<K: Ord>(mut anchor: &mut Anchor<K>) {
-            loop {
-                let node = anchor.as_mut().unwrap();
-                let mut max_priority = node.priority;
-                let mut max_priority_dir = 2;
-                if let Some(child) = &node.children[0] {
-                    if child.priority > max_priority {
-                        max_priority = child.priority;
-                        max_priority_dir = 0;
-                    }
+        }
+        0
+    }
+
+    pub fn remove(&mut self, key: K) {
+        fn aux<K: Ord>(anchor: &mut Anchor<K>, key: K, rotations: &mut u64) {
+            let Some(node) = anchor else {
+                return;
+            };
+            match key.cmp(&node.key) {
+                Ordering::Less => {
+                    aux(&mut node.children[0], key, rotations);
+                    node.update_count();
                 }
-                if let Some(child) = &node.children[1] {
-                    if child.priority > max_priority {
-                        // max_priority = child.priority;
-                        max_priority_dir = 1;
-                    }
+                Ordering::Greater => {
+                    aux(&mut node.children[1], key, rotations);
+                    node.update_count();
                 }
-                if max_priority_dir == 2 {
-                    break;
+                Ordering::Equal => {
+                    remove_root(anchor, rotations);
                 }
-                Treap::rotate(anchor, max_priority_dir);
-                anchor = &mut anchor.as_mut().unwrap().children[1 - max_priority_dir];
             }
         }
-        fn aux<K: Ord>(anchor: &mut Anchor<K>, key: K) {
+        let mut rotations = 0;
+        aux(&mut self.root, key, &mut rotations);
+        #[cfg(feature = "profiling")]
+        {
+            self.rotations += rotations;
+        }
+        self.check();
+    }
+
+    // remove the k-th smallest key
+    pub fn remove_rank(&mut self, k: usize) -> Option<K> {
+        fn aux<K: Ord>(anchor: &mut Anchor<K>, k: usize, rotations: &mut u64) -> K {
+            let node = anchor.as_mut().unwrap();
+            let left_count = count(&node.children[0]);
+            let key = match k.cmp(&left_count) {
+                Ordering::Less => aux(&mut node.children[0], k, rotations),
+                Ordering::Greater => aux(&mut node.children[1], k - left_count - 1, rotations),
+                Ordering::Equal => return remove_root(anchor, rotations),
+            };
+            node.update_count();
+            key
+        }
+        if k >= self.len() {
+            return None;
+        }
+        let mut rotations = 0;
+        let key = aux(&mut self.root, k, &mut rotations);
+        #[cfg(feature = "profiling")]
+        {
+            self.rotations += rotations;
+        }
+        self.check();
+        Some(key)
+    }
+
+    // remove the given keys, in a single walk when they are sorted
+    pub fn remove_all(&mut self, keys: &mut dyn Iterator<Item = K>) {
+        // keys is sorted
+        fn aux<K: Ord>(anchor: &mut Anchor<K>, keys: &[K]) {
             let Some(node) = anchor else {
                 return;
             };
-            match key.cmp(&node.key) {
-                Ordering::Less => aux(&mut node.children[0], key),
-                Ordering::Greater => aux(&mut node.children[1], key),
-                Ordering::Equal => match (node.children[0].take(), node.children[1].take()) {
-                    (None, None) => *anchor = None,
-                    (Some(left), None) => *anchor = Some(left),
-                    (None, Some(right)) => *anchor = Some(right),
-                    (Some(left), Some(mut right)) => {
-                        if right.children[0].is_none() {
-                            right.children[0] = Some(left);
-                            *anchor = Some(right);
-                            bubble_down(anchor);
-                        } else {
-                            let mut new_node = leftmost(&mut right);
-                            new_node.children[0] = Some(left);
-                            new_node.children[1] = Some(right);
-                            *anchor = Some(new_node);
-                            bubble_down(anchor);
-                        }
-                    }
-                },
+            if keys.is_empty() {
+                return;
+            }
+            let (left_keys, found, right_keys) = match keys.binary_search(&node.key) {
+                Ok(index) => (&keys[..index], true, &keys[index + 1..]),
+                Err(index) => (&keys[..index], false, &keys[index..]),
+            };
+            aux(&mut node.children[0], left_keys);
+            aux(&mut node.children[1], right_keys);
+            if found {
+                let [left, right] = std::mem::take(&mut node.children);
+                *anchor = join(left, right);
+            } else {
+                node.update_count();
+            }
+        }
+        let keys: Vec<K> = keys.collect();
+        if keys.windows(2).all(|w| w[0] <= w[1]) {
+            aux(&mut self.root, &keys);
+            self.check();
+        } else {
+            for key in keys {
+                self.remove(key);
             }
         }
-        aux(&mut self.root, key);
+    }
+
+    // the root has the highest priority, which makes it a uniformly random key
+    pub fn pop_random(&mut self) -> Option<K> {
+        self.root.as_ref()?;
+        let mut rotations = 0;
+        let key = remove_root(&mut self.root, &mut rotations);
+        #[cfg(feature = "profiling")]
+        {
+            self.rotations += rotations;
+        }
         self.check();
+        Some(key)
+    }
+
+    pub fn merge_overlapping(mut self, other: Treap<K>) -> Treap<K> {
+        self.root = union(self.root.take(), other.root);
+        self.check();
+        self
+    }
+
+    pub fn remove_range<R: RangeBounds<K>>(&mut self, range: R) {
+        let below_start = |key: &K| match range.start_bound() {
+            Bound::Included(start) => key < start,
+            Bound::Excluded(start) => key <= start,
+            Bound::Unbounded => false,
+        };
+        let below_end = |key: &K| match range.end_bound() {
+            Bound::Included(end) => key <= end,
+            Bound::Excluded(end) => key < end,
+            Bound::Unbounded => true,
+        };
+        let (left, rest) = split_where(self.root.take(), &below_start);
+        let (_, right) = split_where(rest, &below_end);
+        self.root = join(left, right);
+        self.check();
+    }
+
+    // walk both treaps in order, pairing equal keys
+    pub fn outer_join<'a>(
+        &'a self,
+        other: &'a Treap<K>,
+    ) -> impl Iterator<Item = (Option<&'a K>, Option<&'a K>)> {
+        merge_join(self.iter().peekable(), other.iter().peekable())
+    }
+
+    // split into the k smallest keys and the rest
+    pub fn split_at_rank(mut self, k: usize) -> (Treap<K>, Treap<K>) {
+        let (left, right_root) = split_rank(self.root.take(), k);
+        self.root = left;
+        let mut right = Treap::with_rng(self.fork_rng());
+        right.root = right_root;
+        self.check();
+        right.check();
+        (self, right)
+    }
+}
+
+// split a subtree into the keys for which in_left holds and the rest
+// in_left must hold for a prefix of the keys
+fn split_where<K, F: Fn(&K) -> bool>(anchor: Anchor<K>, in_left: &F) -> (Anchor<K>, Anchor<K>) {
+    let Some(mut node) = anchor else {
+        return (None, None);
+    };
+    if in_left(&node.key) {
+        let (left, right) = split_where(node.children[1].take(), in_left);
+        node.children[1] = left;
+        node.update_count();
+        (Some(node), right)
+    } else {
+        let (left, right) = split_where(node.children[0].take(), in_left);
+        node.children[0] = right;
+        node.update_count();
+        (left, Some(node))
+    }
+}
+
+// concatenate two subtrees, the keys of left being lower than the keys of right
+fn join<K>(left: Anchor<K>, right: Anchor<K>) -> Anchor<K> {
+    match (left, right) {
+        (None, right) => right,
+        (left, None) => left,
+        (Some(mut left), Some(right)) if left.priority > right.priority => {
+            left.children[1] = join(left.children[1].take(), Some(right));
+            left.update_count();
+            Some(left)
+        }
+        (left, Some(mut right)) => {
+            right.children[0] = join(left, right.children[0].take());
+            right.update_count();
+            Some(right)
+        }
+    }
+}
+
+// split a subtree into its k leftmost nodes and the rest
+fn split_rank<K>(anchor: Anchor<K>, k: usize) -> (Anchor<K>, Anchor<K>) {
+    let Some(mut node) = anchor else {
+        return (None, None);
+    };
+    let left_count = count(&node.children[0]);
+    if k <= left_count {
+        let (left, right) = split_rank(node.children[0].take(), k);
+        node.children[0] = right;
+        node.update_count();
+        (left, Some(node))
+    } else {
+        let (left, right) = split_rank(node.children[1].take(), k - left_count - 1);
+        node.children[1] = left;
+        node.update_count();
+        (Some(node), right)
+    }
+}
+
+// split a subtree into the keys lower than key and the keys greater than key
+// the middle value is true when key was present (its node is dropped)
+fn split<K: Ord>(anchor: Anchor<K>, key: &K) -> (Anchor<K>, bool, Anchor<K>) {
+    let Some(mut node) = anchor else {
+        return (None, false, None);
+    };
+    match key.cmp(&node.key) {
+        Ordering::Less => {
+            let (left, found, right) = split(node.children[0].take(), key);
+            node.children[0] = right;
+            node.update_count();
+            (left, found, Some(node))
+        }
+        Ordering::Greater => {
+            let (left, found, right) = split(node.children[1].take(), key);
+            node.children[1] = left;
+            node.update_count();
+            (Some(node), found, right)
+        }
+        Ordering::Equal => {
+            let [left, right] = node.children;
+            (left, true, right)
+        }
+    }
+}
+
+// merge two subtrees whose key ranges may interleave, dropping duplicates
+fn union<K: Ord>(a: Anchor<K>, b: Anchor<K>) -> Anchor<K> {
+    let (mut a, b) = match (a, b) {
+        (None, b) => return b,
+        (a, None) => return a,
+        (Some(a), Some(b)) if a.priority < b.priority => (b, Some(a)),
+        (Some(a), b) => (a, b),
+    };
+    // the root with the highest priority stays on top
+    let (left, _, right) = split(b, &a.key);
+    let [a_left, a_right] = std::mem::take(&mut a.children);
+    a.children = [union(a_left, left), union(a_right, right)];
+    a.update_count();
+    Some(a)
+}
+
+fn leftmost<K>(mut node: &mut Node<K>) -> Box<Node<K>> {
+    while node.children[0].as_ref().unwrap().children[0].is_some() {
+        node.count -= 1;
+        node = node.children[0].as_mut().unwrap();
+    }
+    node.count -= 1;
+    let mut ret = node.children[0].take().unwrap();
+    node.children[0] = ret.children[1].take();
+    assert!(ret.children[0].is_none());
+    assert!(ret.children[1].is_none());
+    ret
+}
+
+fn bubble_down<K: Ord>(mut anchor: &mut Anchor<K>, rotations: &mut u64) {
+    loop {
+        let node = anchor.as_mut().unwrap();
+        let mut max_priority = node.priority;
+        let mut max_priority_dir = 2;
+        if let Some(child) = &node.children[0] {
+            if child.priority > max_priority {
+                max_priority = child.priority;
+                max_priority_dir = 0;
+            }
+        }
+        if let Some(child) = &node.children[1] {
+            if child.priority > max_priority {
+                // max_priority = child.priority;
+                max_priority_dir = 1;
+            }
+        }
+        if max_priority_dir == 2 {
+            break;
+        }
+        Treap::rotate(anchor, max_priority_dir, rotations);
+        anchor = &mut anchor.as_mut().unwrap().children[1 - max_priority_dir];
+    }
+}
+
+// remove the top node of a non-empty subtree and return its key
+fn remove_root<K: Ord>(anchor: &mut Anchor<K>, rotations: &mut u64) -> K {
+    let mut node = anchor.take().unwrap();
+    match (node.children[0].take(), node.children[1].take()) {
+        (None, None) => {}
+        (Some(left), None) => *anchor = Some(left),
+        (None, Some(right)) => *anchor = Some(right),
+        (Some(left), Some(mut right)) => {
+            if right.children[0].is_none() {
+                right.children[0] = Some(left);
+                right.update_count();
+                *anchor = Some(right);
+                bubble_down(anchor, rotations);
+            } else {
+                let mut new_node = leftmost(&mut right);
+                new_node.children[0] = Some(left);
+                new_node.children[1] = Some(right);
+                new_node.update_count();
+                *anchor = Some(new_node);
+                bubble_down(anchor, rotations);
+            }
+        }
     }
+    node.key
 }
 
 // non-consuming iterator
@@ -213,35 +680,39 @@ impl<'a, K> IterRef<'a, K> {
             IterRef { stack: vec![] }
         }
     }
-}
 
-impl<'a, K> Iterator for IterRef<'a, K> {
-    type Item = &'a K;
-    fn next(&mut self) -> Option<Self::Item> {
+    fn next_node(&mut self) -> Option<&'a Node<K>> {
         let (explored, node) = self.stack.pop()?;
         #[allow(clippy::collapsible_else_if)] // clearer to see the two cases this way
         if explored {
             if let Some(child) = &node.children[1] {
                 self.stack.push((false, child));
-                Some(&node.key)
+                Some(node)
             } else {
-                Some(&node.key)
+                Some(node)
             }
         } else {
             if let Some(child) = &node.children[0] {
                 self.stack.push((true, node));
                 self.stack.push((false, child));
-                self.next()
+                self.next_node()
             } else if let Some(child) = &node.children[1] {
                 self.stack.push((false, child));
-                Some(&node.key)
+                Some(node)
             } else {
-                Some(&node.key)
+                Some(node)
             }
         }
     }
 }
 
+impl<'a, K> Iterator for IterRef<'a, K> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(&self.next_node()?.key)
+    }
+}
+
 impl<'a, K> IntoIterator for &'a Treap<K> {
     type IntoIter = IterRef<'a, K>;
     type Item = &'a K;
@@ -293,9 +764,30 @@ impl<K> IntoIterator for Treap<K> {
 }
 
 impl<K> Treap<K> {
-    pub fn iter(&self) -> IterRef<K> {
+    // append the keys in order, reusing the allocation of buf
+    pub fn flatten_into(self, buf: &mut Vec<K>) {
+        buf.extend(self);
+    }
+}
+
+impl<K> Treap<K> {
+    pub fn iter(&self) -> IterRef<'_, K> {
         self.into_iter()
     }
+
+    // append references to the keys in order, reusing the allocation of buf
+    pub fn as_sorted_slice_via<'a>(&'a self, buf: &mut Vec<&'a K>) {
+        buf.extend(self.iter());
+    }
+
+    // in-order keys with the priorities of their nodes, for debugging
+    pub fn iter_with_priority(&self) -> impl Iterator<Item = (&K, u64)> {
+        let mut iter = IterRef::new(self);
+        std::iter::from_fn(move || {
+            let node = iter.next_node()?;
+            Some((&node.key, node.priority))
+        })
+    }
 }
 
 #[cfg(test)]
@@ -355,4 +847,399 @@ mod tests {
         let actual: HashSet<_> = tree.iter().copied().collect();
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn merge_overlapping() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut a = super::Treap::new();
+        let mut b = super::Treap::new();
+        let mut expected = HashSet::new();
+        for _ in 0..1000 {
+            let x: u16 = rng.gen_range(0..2000);
+            a.insert(x);
+            expected.insert(x);
+            let x: u16 = rng.gen_range(1000..3000);
+            b.insert(x);
+            expected.insert(x);
+        }
+        let merged = a.merge_overlapping(b);
+        merged.check();
+        let actual: Vec<_> = merged.iter().copied().collect();
+        let mut expected: Vec<_> = expected.into_iter().collect();
+        expected.sort();
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn depth() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut tree = super::Treap::new();
+        assert_eq!(tree.height(), 0);
+        assert_eq!(tree.avg_depth(), 0.0);
+        tree.insert(0);
+        assert_eq!(tree.height(), 1);
+        assert_eq!(tree.avg_depth(), 0.0);
+        for _ in 0..10000 {
+            let x: u64 = rng.gen();
+            tree.insert(x);
+        }
+        assert!(tree.height() < 60);
+        assert!(tree.avg_depth() < 30.0);
+    }
+
+    #[test]
+    fn split_at_rank() {
+        let mut tree = super::Treap::new();
+        for x in (0..100).rev() {
+            tree.insert(x);
+        }
+        assert_eq!(tree.len(), 100);
+        let (left, right) = tree.split_at_rank(30);
+        assert_eq!((left.len(), right.len()), (30, 70));
+        let actual: Vec<_> = left.iter().chain(right.iter()).copied().collect();
+        assert_eq!(actual, (0..100).collect::<Vec<_>>());
+
+        let (left, right) = right.split_at_rank(1000);
+        assert_eq!((left.len(), right.len()), (70, 0));
+        let (left, right) = left.split_at_rank(0);
+        assert_eq!((left.len(), right.len()), (0, 70));
+    }
+
+    #[test]
+    fn pop_random() {
+        let mut tree = super::Treap::new();
+        assert_eq!(tree.pop_random(), None);
+        for x in 0..100 {
+            tree.insert(x);
+        }
+        let mut popped = HashSet::new();
+        while let Some(x) = tree.pop_random() {
+            assert!(!tree.contains(x));
+            popped.insert(x);
+        }
+        assert_eq!(popped, (0..100).collect());
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn from_key_priority() {
+        // a chain going right
+        let mut tree = super::Treap::from_key_priority((0..10).map(|x| (x, 100 - x)).collect());
+        assert_eq!(tree.height(), 10);
+        tree.remove(5);
+        assert_eq!(tree.height(), 9);
+
+        //       4
+        //    2     8
+        //   1 3   6 9
+        //        5 7
+        let pairs = vec![
+            (4, 100),
+            (2, 90),
+            (8, 95),
+            (1, 80),
+            (3, 80),
+            (6, 80),
+            (9, 80),
+            (5, 70),
+            (7, 70),
+        ];
+        let mut tree = super::Treap::from_key_priority(pairs);
+        assert_eq!(tree.height(), 4);
+        // the replacement comes from the leftmost node of the right subtree
+        tree.remove(4);
+        assert_eq!(tree.pop_random(), Some(8));
+        let actual: Vec<_> = tree.iter().copied().collect();
+        assert_eq!(actual, vec![1, 2, 3, 5, 6, 7, 9]);
+    }
+
+    #[test]
+    fn flatten_into() {
+        let mut buf = Vec::with_capacity(100);
+        let capacity = buf.capacity();
+        for range in [0..50, 50..100] {
+            let mut tree = super::Treap::new();
+            for x in range.rev() {
+                tree.insert(x);
+            }
+            tree.flatten_into(&mut buf);
+        }
+        assert_eq!(buf, (0..100).collect::<Vec<_>>());
+        assert_eq!(buf.capacity(), capacity);
+    }
+
+    #[test]
+    fn seeded() {
+        let mut a = super::Treap::seeded(42);
+        let mut b = super::Treap::seeded(42);
+        let mut c = super::Treap::randomized();
+        for x in 0..100 {
+            a.insert(x);
+            b.insert(x);
+            c.insert(x);
+        }
+        assert!(a.same_shape(&b));
+        a.remove(50);
+        assert!(!a.same_shape(&b));
+        b.remove(50);
+        assert!(a.same_shape(&b));
+        let (_, a) = a.split_at_rank(10);
+        let (_, b) = b.split_at_rank(10);
+        assert!(a.same_shape(&b));
+        c.check();
+    }
+
+    #[test]
+    fn iter_with_priority() {
+        // rebuild the tree from the in-order priorities, the highest priority being the root
+        // returns the height and the sum of the depths
+        fn aux(pairs: &[(&u64, u64)], depth: usize, parent_priority: u64) -> (usize, usize) {
+            let Some(root) = (0..pairs.len()).max_by_key(|&i| pairs[i].1) else {
+                return (0, 0);
+            };
+            assert!(pairs[root].1 <= parent_priority);
+            let (left_height, left_depths) = aux(&pairs[..root], depth + 1, pairs[root].1);
+            let (right_height, right_depths) = aux(&pairs[root + 1..], depth + 1, pairs[root].1);
+            (
+                1 + left_height.max(right_height),
+                depth + left_depths + right_depths,
+            )
+        }
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut tree = super::Treap::seeded(42);
+        for _ in 0..1000 {
+            tree.insert(rng.gen::<u64>());
+        }
+        let pairs: Vec<_> = tree.iter_with_priority().collect();
+        assert!(pairs.iter().map(|&(key, _)| key).eq(tree.iter()));
+        let (height, depths) = aux(&pairs, 0, u64::MAX);
+        assert_eq!(height, tree.height());
+        assert_eq!(depths as f64 / pairs.len() as f64, tree.avg_depth());
+    }
+
+    #[test]
+    fn remove_range() {
+        use std::collections::BTreeSet;
+        use std::ops::Bound;
+        let mut tree = super::Treap::seeded(42);
+        let mut expected = BTreeSet::new();
+        for x in 0..1000 {
+            tree.insert(x);
+            expected.insert(x);
+        }
+        let ranges = [
+            (Bound::Included(100), Bound::Excluded(200)),
+            (Bound::Excluded(300), Bound::Included(400)),
+            (Bound::Included(500), Bound::Included(500)),
+            (Bound::Excluded(600), Bound::Excluded(601)),
+            (Bound::Unbounded, Bound::Excluded(10)),
+            (Bound::Included(990), Bound::Unbounded),
+        ];
+        for range in ranges {
+            tree.remove_range(range);
+            expected.retain(|x| !std::ops::RangeBounds::contains(&range, x));
+            assert!(tree.iter().eq(expected.iter()));
+        }
+        assert_eq!(tree.len(), 1000 - 100 - 100 - 1 - 10 - 10);
+        tree.remove_range(..);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn outer_join() {
+        let mut a = super::Treap::seeded(1);
+        for x in [1, 3, 5, 7] {
+            a.insert(x);
+        }
+        let mut b = super::Treap::seeded(2);
+        for x in [3, 4, 7, 8] {
+            b.insert(x);
+        }
+        let actual: Vec<_> = a
+            .outer_join(&b)
+            .map(|(x, y)| (x.copied(), y.copied()))
+            .collect();
+        let expected = vec![
+            (Some(1), None),
+            (Some(3), Some(3)),
+            (None, Some(4)),
+            (Some(5), None),
+            (Some(7), Some(7)),
+            (None, Some(8)),
+        ];
+        assert_eq!(actual, expected);
+        let empty = super::Treap::<i32>::new();
+        assert_eq!(empty.outer_join(&empty).count(), 0);
+    }
+
+    #[test]
+    fn count() {
+        let mut tree = super::Treap::seeded(42);
+        for x in [5, 4, 2, 3, 9, 6, 8, 5] {
+            tree.insert(x);
+        }
+        for x in 0..10 {
+            assert_eq!(tree.count(&x), tree.contains(x) as usize);
+        }
+        assert_eq!(tree.count(&5), 1);
+        assert_eq!(tree.count(&7), 0);
+    }
+
+    #[test]
+    fn max_depth() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut tree = super::Treap::seeded(1);
+        assert_eq!(tree.max_depth(), 0);
+        for _ in 0..10000 {
+            tree.insert(rng.gen::<u64>());
+        }
+        assert_eq!(tree.max_depth(), tree.height() - 1);
+        // the expected depth is about 2 ln n
+        assert!(tree.max_depth() < 50);
+        #[cfg(feature = "profiling")]
+        {
+            assert!(tree.worst_insert_depth() >= 10);
+            assert!(tree.worst_insert_depth() < 50);
+        }
+    }
+
+    #[test]
+    fn remove_all() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut tree = super::Treap::seeded(1);
+        let mut expected = HashSet::new();
+        for x in 0..10000 {
+            tree.insert(x);
+            expected.insert(x);
+        }
+
+        // unsorted
+        let keys: Vec<u64> = (0..1000).map(|_| rng.gen_range(0..20000)).collect();
+        tree.remove_all(&mut keys.iter().copied());
+        for key in &keys {
+            expected.remove(key);
+        }
+        let actual: HashSet<_> = tree.iter().copied().collect();
+        assert_eq!(actual, expected);
+
+        // sorted, with duplicates and absent keys
+        let mut keys: Vec<u64> = (0..1000).map(|_| rng.gen_range(0..20000)).collect();
+        keys.sort();
+        tree.remove_all(&mut keys.iter().copied());
+        for key in &keys {
+            expected.remove(key);
+        }
+        let actual: HashSet<_> = tree.iter().copied().collect();
+        assert_eq!(actual, expected);
+        assert_eq!(tree.len(), expected.len());
+    }
+
+    #[test]
+    fn peek_root() {
+        let mut tree = super::Treap::new();
+        assert_eq!(tree.peek_root(), None);
+        for x in 0..100 {
+            tree.insert(x);
+        }
+        let root = *tree.peek_root().unwrap();
+        assert!(tree.contains(root));
+        assert_eq!(tree.pop_random(), Some(root));
+    }
+
+    #[cfg(feature = "profiling")]
+    #[test]
+    fn rotation_count() {
+        // on ascending keys, a treap rotates about as often as an AVL tree, nearly once per key
+        let mut tree = super::Treap::seeded(1);
+        let mut avl = crate::avl::Avl::new();
+        for x in 0..10000 {
+            tree.insert(x);
+            avl.insert(x);
+        }
+        let ascending = tree.rotation_count();
+        assert!(ascending > 9000 && ascending < 10000);
+        assert!(ascending.abs_diff(avl.rotation_count()) < 100);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut tree = super::Treap::seeded(1);
+        for _ in 0..10000 {
+            tree.insert(rng.gen::<u64>());
+        }
+        assert!(tree.rotation_count() < 3 * 10000);
+        let before = tree.rotation_count();
+        while tree.pop_random().is_some() {}
+        assert!(tree.rotation_count() > before);
+    }
+
+    #[test]
+    fn validate() {
+        use super::{Node, Treap, TreapError};
+        // build a treap from (key, priority, count) triples, root first, without any check
+        fn unchecked(root: (i32, u64, usize), left: (i32, u64, usize)) -> Treap<i32> {
+            let mut node = Box::new(Node::new(root.0, root.1));
+            let mut child = Box::new(Node::new(left.0, left.1));
+            child.count = left.2;
+            node.children[0] = Some(child);
+            node.count = root.2;
+            let mut treap = Treap::new();
+            treap.root = Some(node);
+            treap
+        }
+        assert_eq!(unchecked((5, 10, 2), (3, 5, 1)).validate(), Ok(()));
+        assert_eq!(
+            unchecked((5, 10, 2), (7, 5, 1)).validate(),
+            Err(TreapError::KeyOrder)
+        );
+        assert_eq!(
+            unchecked((5, 10, 2), (3, 15, 1)).validate(),
+            Err(TreapError::PriorityOrder)
+        );
+        assert_eq!(
+            unchecked((5, 10, 3), (3, 5, 1)).validate(),
+            Err(TreapError::Count)
+        );
+        assert_eq!(super::Treap::<i32>::new().validate(), Ok(()));
+    }
+
+    #[test]
+    fn remove_rank() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut tree = super::Treap::seeded(1);
+        let mut expected = Vec::new();
+        for _ in 0..1000 {
+            let x: u64 = rng.gen();
+            tree.insert(x);
+            expected.push(x);
+        }
+        expected.sort();
+        expected.dedup();
+        assert_eq!(tree.remove_rank(tree.len()), None);
+        // the median
+        let median = tree.remove_rank(tree.len() / 2);
+        assert_eq!(median, Some(expected.remove(expected.len() / 2)));
+        for x in expected {
+            assert_eq!(tree.remove_rank(0), Some(x));
+        }
+        assert!(tree.is_empty());
+        assert_eq!(tree.remove_rank(0), None);
+    }
+
+    #[test]
+    fn as_sorted_slice_via() {
+        let mut tree = super::Treap::new();
+        for x in [5, 4, 2, 3, 9, 6, 8] {
+            tree.insert(x);
+        }
+        let mut buf = Vec::with_capacity(16);
+        let capacity = buf.capacity();
+        for _ in 0..2 {
+            buf.clear();
+            tree.as_sorted_slice_via(&mut buf);
+            assert_eq!(buf, [&2, &3, &4, &5, &6, &8, &9]);
+            assert!(buf
+                .iter()
+                .zip(tree.iter())
+                .all(|(a, b)| std::ptr::eq(*a, b)));
+            assert_eq!(buf.capacity(), capacity);
+        }
+    }
 }
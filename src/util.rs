@@ -0,0 +1,45 @@
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::ops::{Bound, RangeBounds};
+
+// convert a range of positions to [start, end), panicking like slices when out of bounds
+pub(crate) fn range_bounds<R: RangeBounds<usize>>(range: R, len: usize) -> (usize, usize) {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start + 1,
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end + 1,
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    assert!(start <= end, "range start {start} is after range end {end}");
+    assert!(
+        end <= len,
+        "range end {end} is out of bounds for length {len}"
+    );
+    (start, end)
+}
+
+// walk two sorted iterators together, pairing equal items
+pub(crate) fn merge_join<T, L, R>(
+    mut left: Peekable<L>,
+    mut right: Peekable<R>,
+) -> impl Iterator<Item = (Option<T>, Option<T>)>
+where
+    T: Ord,
+    L: Iterator<Item = T>,
+    R: Iterator<Item = T>,
+{
+    std::iter::from_fn(move || match (left.peek(), right.peek()) {
+        (None, None) => None,
+        (Some(_), None) => Some((left.next(), None)),
+        (None, Some(_)) => Some((None, right.next())),
+        (Some(a), Some(b)) => match a.cmp(b) {
+            Ordering::Less => Some((left.next(), None)),
+            Ordering::Greater => Some((None, right.next())),
+            Ordering::Equal => Some((left.next(), right.next())),
+        },
+    })
+}
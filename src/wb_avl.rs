@@ -0,0 +1,331 @@
+use std::cmp::Ordering;
+
+type Anchor<K> = Option<Box<Node<K>>>;
+
+struct Node<K> {
+    key: K,
+    count: usize,
+    children: [Anchor<K>; 2],
+}
+
+impl<K> Node<K> {
+    fn new(key: K) -> Self {
+        Node {
+            key,
+            count: 1,
+            children: [None, None],
+        }
+    }
+
+    // must be called whenever the children change
+    fn update_count(&mut self) {
+        self.count = 1 + count(&self.children[0]) + count(&self.children[1]);
+    }
+}
+
+fn count<K>(anchor: &Anchor<K>) -> usize {
+    anchor.as_ref().map_or(0, |node| node.count)
+}
+
+// weight-balanced tree: each child holds at least a quarter of the weight of its parent,
+// where the weight of a subtree is its number of nodes plus one
+// instead of rotating, unbalanced subtrees are rebuilt into perfectly balanced ones
+pub struct WbAvl<K> {
+    root: Anchor<K>,
+}
+
+impl<K> WbAvl<K> {
+    pub fn new() -> Self {
+        WbAvl { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        count(&self.root)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+}
+
+impl<K> Default for WbAvl<K> {
+    fn default() -> Self {
+        WbAvl::new()
+    }
+}
+
+fn is_balanced<K>(node: &Node<K>) -> bool {
+    let weight = node.count + 1;
+    node.children
+        .iter()
+        .all(|child| 4 * (count(child) + 1) >= weight)
+}
+
+// rebuild the subtree into a perfectly balanced one if needed, reusing its nodes
+fn rebalance<K>(anchor: &mut Anchor<K>) {
+    // builds a subtree from the n next nodes
+    fn build<K>(nodes: &mut impl Iterator<Item = Box<Node<K>>>, n: usize) -> Anchor<K> {
+        if n == 0 {
+            return None;
+        }
+        let left = build(nodes, n / 2);
+        let mut node = nodes.next().unwrap();
+        let right = build(nodes, (n - 1) / 2);
+        node.children = [left, right];
+        node.update_count();
+        Some(node)
+    }
+    // detach the nodes in order
+    fn flatten<K>(anchor: Anchor<K>, nodes: &mut Vec<Box<Node<K>>>) {
+        let Some(mut node) = anchor else {
+            return;
+        };
+        let [left, right] = std::mem::take(&mut node.children);
+        flatten(left, nodes);
+        nodes.push(node);
+        flatten(right, nodes);
+    }
+    let Some(node) = anchor else {
+        return;
+    };
+    if is_balanced(node) {
+        return;
+    }
+    let mut nodes = Vec::with_capacity(node.count);
+    flatten(anchor.take(), &mut nodes);
+    let n = nodes.len();
+    *anchor = build(&mut nodes.into_iter(), n);
+}
+
+impl<K: std::fmt::Display> WbAvl<K> {
+    pub fn print(&self) {
+        fn aux<K: std::fmt::Display>(anchor: &Anchor<K>, indent: usize) {
+            let prefix = "    ".repeat(indent);
+            if let Some(node) = anchor {
+                println!("{}- {} ({})", prefix, node.key, node.count);
+                aux(&node.children[0], indent + 1);
+                aux(&node.children[1], indent + 1);
+            } else {
+                println!("{}-", prefix);
+            }
+        }
+        aux(&self.root, 0);
+    }
+}
+
+impl<K: Ord> WbAvl<K> {
+    fn check(&self) {
+        fn aux<K: Ord>(anchor: &Anchor<K>, min: Option<&K>, max: Option<&K>) -> usize {
+            let Some(node) = anchor else {
+                return 0;
+            };
+            if let Some(min) = min {
+                assert!(node.key > *min);
+            }
+            if let Some(max) = max {
+                assert!(node.key < *max);
+            }
+            let left = aux(&node.children[0], min, Some(&node.key));
+            let right = aux(&node.children[1], Some(&node.key), max);
+            assert_eq!(node.count, left + right + 1);
+            assert!(is_balanced(node));
+            node.count
+        }
+        aux(&self.root, None, None);
+    }
+
+    pub fn contains(&self, key: K) -> bool {
+        let mut anchor = &self.root;
+        while let Some(node) = anchor {
+            match key.cmp(&node.key) {
+                Ordering::Less => anchor = &node.children[0],
+                Ordering::Greater => anchor = &node.children[1],
+                Ordering::Equal => return true,
+            }
+        }
+        false
+    }
+
+    pub fn insert(&mut self, key: K) {
+        // returns whether the key was inserted
+        fn aux<K: Ord>(anchor: &mut Anchor<K>, key: K) -> bool {
+            let Some(node) = anchor else {
+                *anchor = Some(Box::new(Node::new(key)));
+                return true;
+            };
+            let dir = match key.cmp(&node.key) {
+                Ordering::Less => 0,
+                Ordering::Greater => 1,
+                Ordering::Equal => return false,
+            };
+            if !aux(&mut node.children[dir], key) {
+                return false;
+            }
+            node.update_count();
+            rebalance(anchor);
+            true
+        }
+        aux(&mut self.root, key);
+        self.check();
+    }
+
+    pub fn remove(&mut self, key: K) {
+        // detach the leftmost node of a non-empty subtree
+        fn pop_leftmost<K>(anchor: &mut Anchor<K>) -> Box<Node<K>> {
+            let node = anchor.as_mut().unwrap();
+            if node.children[0].is_none() {
+                let mut node = anchor.take().unwrap();
+                *anchor = node.children[1].take();
+                return node;
+            }
+            let ret = pop_leftmost(&mut node.children[0]);
+            node.update_count();
+            rebalance(anchor);
+            ret
+        }
+        // returns whether the key was removed
+        fn aux<K: Ord>(anchor: &mut Anchor<K>, key: K) -> bool {
+            let Some(node) = anchor else {
+                return false;
+            };
+            let dir = match key.cmp(&node.key) {
+                Ordering::Less => 0,
+                Ordering::Greater => 1,
+                Ordering::Equal => {
+                    let mut node = anchor.take().unwrap();
+                    let [left, mut right] = std::mem::take(&mut node.children);
+                    if right.is_none() {
+                        *anchor = left;
+                        return true;
+                    }
+                    // replace the node with its successor
+                    let mut successor = pop_leftmost(&mut right);
+                    successor.children = [left, right];
+                    successor.update_count();
+                    *anchor = Some(successor);
+                    rebalance(anchor);
+                    return true;
+                }
+            };
+            if !aux(&mut node.children[dir], key) {
+                return false;
+            }
+            node.update_count();
+            rebalance(anchor);
+            true
+        }
+        aux(&mut self.root, key);
+        self.check();
+    }
+}
+
+impl<K: Ord> FromIterator<K> for WbAvl<K> {
+    fn from_iter<T>(iter: T) -> Self
+    where
+        T: IntoIterator<Item = K>,
+    {
+        let mut tree = WbAvl::new();
+        for x in iter {
+            tree.insert(x);
+        }
+        tree
+    }
+}
+
+// non-consuming iterator
+pub struct IterRef<'a, K> {
+    stack: Vec<&'a Node<K>>,
+}
+
+impl<'a, K> IterRef<'a, K> {
+    fn descend(&mut self, mut anchor: &'a Anchor<K>) {
+        while let Some(node) = anchor {
+            self.stack.push(node);
+            anchor = &node.children[0];
+        }
+    }
+}
+
+impl<'a, K> Iterator for IterRef<'a, K> {
+    type Item = &'a K;
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.descend(&node.children[1]);
+        Some(&node.key)
+    }
+}
+
+impl<'a, K> IntoIterator for &'a WbAvl<K> {
+    type Item = &'a K;
+    type IntoIter = IterRef<'a, K>;
+    fn into_iter(self) -> Self::IntoIter {
+        let mut iter = IterRef { stack: Vec::new() };
+        iter.descend(&self.root);
+        iter
+    }
+}
+
+impl<K> WbAvl<K> {
+    pub fn iter(&self) -> IterRef<'_, K> {
+        self.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::seq::IteratorRandom;
+    use rand::{Rng, SeedableRng};
+    use std::collections::HashSet;
+
+    #[test]
+    fn test() {
+        let mut tree = super::WbAvl::new();
+        for x in [5, 4, 2, 3, 9, 6, 8, 5] {
+            tree.insert(x);
+        }
+        tree.print();
+        assert_eq!(tree.len(), 7);
+        assert!(tree.contains(6));
+        assert!(!tree.contains(7));
+        for x in [5, 7, 2] {
+            tree.remove(x);
+        }
+        assert!(tree.iter().eq([&3, &4, &6, &8, &9]));
+        for x in [3, 4, 6, 8, 9] {
+            tree.remove(x);
+        }
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn big_test() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+        let mut tree = super::WbAvl::new();
+        let mut expected = HashSet::new();
+
+        // try to unbalance the tree
+        for x in 0..10000 {
+            tree.insert(x);
+            expected.insert(x);
+        }
+
+        // add some more
+        for _ in 0..10000 {
+            let x: u64 = rng.gen();
+            tree.insert(x);
+            expected.insert(x);
+        }
+        let actual: HashSet<_> = tree.iter().copied().collect();
+        assert_eq!(actual, expected);
+
+        // remove some
+        for _ in 0..1000 {
+            let x: u64 = *expected.iter().choose(&mut rng).unwrap();
+            tree.remove(x);
+            expected.remove(&x);
+        }
+        let actual: HashSet<_> = tree.iter().copied().collect();
+        assert_eq!(actual, expected);
+        assert_eq!(tree.len(), expected.len());
+    }
+}